@@ -0,0 +1,241 @@
+use crate::echo;
+use crate::{Error, Result};
+
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Written into every extracted archive to detect changes of the source.
+const MARKER_FILE: &str = ".npack-archive";
+
+/// Everything needed to download and extract a package archive.
+#[derive(Debug, Clone)]
+pub struct Source {
+    /// Url or local path of a `.tar.gz`, `.tgz` or `.zip` file
+    pub location: String,
+    /// Expected sha256 checksum, hex encoded
+    pub sha256: Option<String>,
+    /// Cancel a download running longer than this
+    pub timeout: Option<Duration>,
+    /// Refuse to download anything
+    pub offline: bool,
+    /// Where progress is reported
+    pub progress: Option<echo::Line>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    TarGz,
+    Zip,
+}
+
+impl Source {
+    pub fn new<T: Into<String>>(location: T) -> Source {
+        Source {
+            location: location.into(),
+            sha256: None,
+            timeout: None,
+            offline: false,
+            progress: None,
+        }
+    }
+
+    /// A copy of this source reporting progress on `line`.
+    pub fn with_progress(self, line: echo::Line) -> Source {
+        Source {
+            progress: Some(line),
+            ..self
+        }
+    }
+
+    fn status(&self, msg: &str) {
+        if let Some(line) = self.progress {
+            line.status(msg);
+        }
+    }
+
+    fn format(&self) -> Result<Format> {
+        archive_format(&self.location)
+            .ok_or_else(|| Error::Archive(format!("unknown archive type {}", self.location)))
+    }
+
+    fn is_remote(&self) -> bool {
+        self.location.starts_with("http://") || self.location.starts_with("https://")
+    }
+
+    fn download(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        if !self.is_remote() {
+            let path = self.location.strip_prefix("file://").unwrap_or(&self.location);
+            File::open(path)?.read_to_end(&mut data)?;
+            return Ok(data);
+        }
+        if self.offline {
+            return Err(Error::NotCached(self.location.clone()));
+        }
+        self.status("downloading");
+        let mut agent = ureq::AgentBuilder::new();
+        if let Some(timeout) = self.timeout {
+            agent = agent.timeout(timeout);
+        }
+        let response = agent
+            .build()
+            .get(&self.location)
+            .call()
+            .map_err(|e| Error::Archive(e.to_string()))?;
+        response
+            .into_reader()
+            .read_to_end(&mut data)
+            .map_err(|e| Error::Archive(e.to_string()))?;
+        Ok(data)
+    }
+}
+
+fn archive_format(location: &str) -> Option<Format> {
+    let location = location.split(['?', '#']).next().unwrap_or("");
+    if location.ends_with(".tar.gz") || location.ends_with(".tgz") {
+        Some(Format::TarGz)
+    } else if location.ends_with(".zip") {
+        Some(Format::Zip)
+    } else {
+        None
+    }
+}
+
+/// Check whether `location` names an archive npack can extract.
+pub fn is_archive(location: &str) -> bool {
+    archive_format(location).is_some()
+}
+
+/// Derive a package name from the file name of an archive.
+pub fn name_from_location(location: &str) -> String {
+    let file = location
+        .split(['?', '#'])
+        .next()
+        .unwrap_or("")
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or("");
+    [".tar.gz", ".tgz", ".zip"]
+        .iter()
+        .find_map(|ext| file.strip_suffix(ext))
+        .unwrap_or(file)
+        .to_string()
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Download, verify and extract `source` into `target`, replacing it.
+pub fn install(source: &Source, target: &Path) -> Result<()> {
+    let format = source.format()?;
+    let data = source.download()?;
+    let checksum = sha256_hex(&data);
+    if let Some(ref expected) = source.sha256 {
+        if !expected.eq_ignore_ascii_case(&checksum) {
+            return Err(Error::Checksum(expected.clone(), checksum));
+        }
+    }
+
+    source.status("extracting");
+    let tmp = temp_dir(target);
+    if tmp.exists() {
+        fs::remove_dir_all(&tmp)?;
+    }
+    fs::create_dir_all(&tmp)?;
+    let res = extract(format, &data, &tmp).and_then(|_| {
+        let root = single_root(&tmp)?;
+        File::create(root.join(MARKER_FILE))?
+            .write_all(format!("{}\n{}\n", source.location, checksum).as_bytes())?;
+        if target.exists() {
+            fs::remove_dir_all(target)?;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(&root, target)?;
+        Ok(())
+    });
+    if tmp.exists() {
+        fs::remove_dir_all(&tmp)?;
+    }
+    res
+}
+
+/// Extract `source` again if its location or checksum changed since the last
+/// extraction. Returns whether anything was extracted.
+pub fn update(source: &Source, target: &Path) -> Result<bool> {
+    let mut marker = String::new();
+    if let Ok(mut f) = File::open(target.join(MARKER_FILE)) {
+        f.read_to_string(&mut marker)?;
+    }
+    let mut lines = marker.lines();
+    let location = lines.next().unwrap_or("");
+    let checksum = lines.next().unwrap_or("");
+    let changed = location != source.location
+        || source
+            .sha256
+            .as_ref()
+            .is_some_and(|s| !s.eq_ignore_ascii_case(checksum));
+    if changed {
+        install(source, target)?;
+    }
+    Ok(changed)
+}
+
+fn temp_dir(target: &Path) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(".npack-tmp");
+    target.with_file_name(name)
+}
+
+fn extract(format: Format, data: &[u8], dir: &Path) -> Result<()> {
+    match format {
+        Format::TarGz => {
+            let decoder = flate2::read::GzDecoder::new(Cursor::new(data));
+            tar::Archive::new(decoder).unpack(dir)?;
+        }
+        Format::Zip => {
+            zip::ZipArchive::new(Cursor::new(data))
+                .and_then(|mut a| a.extract(dir))
+                .map_err(|e| Error::Archive(e.to_string()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Release archives usually wrap everything into one top level directory.
+fn single_root(dir: &Path) -> Result<PathBuf> {
+    let entries = dir.read_dir()?.collect::<std::io::Result<Vec<_>>>()?;
+    match entries.as_slice() {
+        [entry] if entry.file_type()?.is_dir() => Ok(entry.path()),
+        _ => Ok(dir.to_path_buf()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archive_names() {
+        assert!(is_archive("https://example.com/plugin-1.0.tar.gz"));
+        assert!(is_archive("/tmp/plugin.zip"));
+        assert!(is_archive("https://example.com/plugin.tgz?download=1"));
+        assert!(!is_archive("https://github.com/user/repo.git"));
+        assert_eq!(name_from_location("https://example.com/v1/plugin-1.0.tar.gz"), "plugin-1.0");
+        assert_eq!(name_from_location("/tmp/plugin.zip"), "plugin");
+    }
+
+    #[test]
+    fn checksum() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -56,6 +56,9 @@ pub struct Install  {
         /// Install local plugins
         #[arg(short, long)]
         pub local: bool,
+        /// Copy a local package instead of linking it
+        #[arg(long)]
+        pub copy: bool,
         /// Command for loading the plugins
         #[arg(long, value_name="LOAD_CMD")]
         pub on: Option<String>,
@@ -69,9 +72,30 @@ pub struct Install  {
         /// Load this plugins for specific types
         #[arg(long, value_name="BUILD_CMD")]
         pub build: Option<String>,
-        /// Load this plugins for specific types
+        /// Branch to check out
         #[arg(long, value_name="BRANCH")]
         pub branch: Option<String>,
+        /// Hold the plugin at a tag
+        #[arg(long, value_name="TAG", conflicts_with_all=["commit", "version"])]
+        pub tag: Option<String>,
+        /// Hold the plugin at a commit
+        #[arg(long, value_name="COMMIT", conflicts_with_all=["tag", "version"])]
+        pub commit: Option<String>,
+        /// Follow the newest tag matching a semver range, e.g. '^1.2'
+        #[arg(long, value_name="VERSION", conflicts_with_all=["tag", "commit"])]
+        pub version: Option<String>,
+        /// Only fetch this many commits of history, 0 fetches everything
+        #[arg(long, value_name="DEPTH")]
+        pub depth: Option<u32>,
+        /// Don't check out submodules
+        #[arg(long)]
+        pub no_submodules: bool,
+        /// Only expose this subdirectory of the repository
+        #[arg(long, value_name="DIR")]
+        pub rtp: Option<String>,
+        /// Expected sha256 checksum of an archive package
+        #[arg(long, value_name="SHA256")]
+        pub sha256: Option<String>,
         /// Installing packages concurrently
         #[arg(
             long,
@@ -80,7 +104,13 @@ pub struct Install  {
             value_parser=none_zeor_parser,
             )]
         pub threads: Option<usize>,
-        pub package: String,
+        /// Check out the commits recorded in the lockfile
+        #[arg(long)]
+        pub locked: bool,
+        /// Install from the mirror cache only
+        #[arg(long)]
+        pub offline: bool,
+        pub package: Option<String>,
     }
 
 #[derive(Args, Debug)]
@@ -96,6 +126,9 @@ pub struct Config{
         /// Delete package configuration file
         #[arg(short, long)]
         pub delete: bool,
+        /// Edit the Lua configuration instead of the Vimscript one
+        #[arg(long)]
+        pub lua: bool,
         pub package: String,
 }
 
@@ -119,6 +152,12 @@ pub struct Update{
         /// Regenerate the '_pack' file (combine all package configurations)
         #[arg(short, long)]
         pub packfile: bool,
+        /// Only show the commits an update would pull in
+        #[arg(short = 'n', long)]
+        pub dry_run: bool,
+        /// Stash local changes and back up local commits instead of skipping
+        #[arg(short, long)]
+        pub force: bool,
         /// Installing packages concurrently
         #[arg(
             long,
@@ -131,6 +170,83 @@ pub struct Update{
         pub package: Vec<String>,
     }
 #[derive(Args, Debug)]
+pub struct Outdated{
+        /// Skip packages
+        #[arg(short, long)]
+        pub skip: Vec<String>,
+        /// Checking packages concurrently
+        #[arg(
+            long,
+            short = 'j',
+            value_name="THREADS",
+            value_parser=none_zeor_parser,
+            )]
+        pub threads: Option<usize>,
+        /// Packages to check, default all
+        pub package: Vec<String>,
+    }
+#[derive(Args, Debug)]
+pub struct Rollback{
+        /// Use the mirror cache only if a commit has to be fetched
+        #[arg(long)]
+        pub offline: bool,
+        /// Stash local changes and back up local commits instead of skipping
+        #[arg(short, long)]
+        pub force: bool,
+        /// Packages to roll back, default all
+        pub package: Vec<String>,
+    }
+#[derive(Args, Debug)]
+pub struct Snapshot{
+        #[command(subcommand)]
+        pub cmd: SnapshotCommand,
+    }
+#[derive(Subcommand, Debug)]
+pub enum SnapshotCommand {
+    /// Save the current plugin set under a name
+    Save {
+        name: String,
+        /// Replace an existing snapshot
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// List saved snapshots
+    List,
+    /// Restore the plugin set saved under a name
+    Restore {
+        name: String,
+        /// Installing packages concurrently
+        #[arg(
+            long,
+            short = 'j',
+            value_name="THREADS",
+            value_parser=none_zeor_parser,
+            )]
+        threads: Option<usize>,
+    },
+}
+#[derive(Args, Debug)]
+pub struct Import{
+        /// Add the packages under provided category
+        #[arg(short, long, value_name="CATEGORY", default_value="default")]
+        pub category: String,
+        /// vimrc, init.lua or lazy-lock.json files to read
+        #[arg(required = true)]
+        pub files: Vec<String>,
+    }
+#[derive(Args, Debug)]
+pub struct Export{
+        /// Plugin manager to write the spec for
+        #[arg(short, long, value_enum)]
+        pub format: ExportFormat,
+        /// Pin packages to the commits recorded in the lockfile
+        #[arg(long)]
+        pub locked: bool,
+        /// Only export packages of this category
+        #[arg(short, long, value_name="CATEGORY")]
+        pub category: Option<String>,
+    }
+#[derive(Args, Debug)]
 pub struct Completions{
         #[arg(value_enum)]
         pub shell: Shell
@@ -140,7 +256,7 @@ pub enum Command {
     /// List installed packages
     List(List),
     /// Install new packages/plugins
-    Install(Install),
+    Install(Box<Install>),
     /// Uninstall packages/plugins
     Uninstall(Uninstall) ,
     /// Configure/edit the package specific configuration
@@ -149,13 +265,36 @@ pub enum Command {
     Move(Move),
     /// Update packages
     Update(Update),
+    /// Show the upstream commits packages are behind
+    Outdated(Outdated),
+    /// Restore packages to their state before the last update
+    Rollback(Rollback),
+    /// Save and restore named snapshots of the whole plugin set
+    Snapshot(Snapshot),
     /// Generate the pack package file
     Generate,
+    /// Compact repositories and remove leftover partial clones
+    Gc,
+    /// Add the plugins of vim-plug, dein, packer or lazy.nvim to the packfile
+    Import(Import),
+    /// Print the packfile as the spec of another plugin manager
+    Export(Export),
     /// Generates completion scripts for your shell
     Completions(Completions),
 }
 
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    VimPlug,
+    Lazy,
+    Packer,
+    #[value(name = "vim.pack")]
+    VimPack,
+    /// Lockfile of vim.pack with the commits in the lockfile
+    NvimPackLock,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum Shell {
     Bash,
@@ -11,7 +11,11 @@ pub fn config(args: Config) -> Result<()> {
     let temp_pack = package::Package::new(&args.package, "temp", true);
     let pack = packs.iter().find(|x| args.package == x.name).unwrap_or(&temp_pack);
 
-    let path = pack.config_path();
+    let path = if args.lua {
+        pack.lua_config_path()
+    } else {
+        pack.config_path()
+    };
 
     let modified = match fs::metadata(&path) {
         Err(e) => {
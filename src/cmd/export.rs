@@ -0,0 +1,28 @@
+use crate::cli::{Export, ExportFormat};
+use crate::export;
+use crate::lock;
+use crate::package;
+use crate::Result;
+
+pub fn exec(args: Export) -> Result<()> {
+    let mut packs = package::fetch()?;
+    if let Some(ref c) = args.category {
+        packs.retain(|p| &p.category == c);
+    }
+    let locked = if args.locked || args.format == ExportFormat::NvimPackLock {
+        lock::fetch()?
+    } else {
+        vec![]
+    };
+
+    let (spec, notes) = export::export(args.format, &packs, &locked);
+    print!("{spec}");
+    // Notes go to stderr so the spec can be redirected into a file.
+    if !notes.is_empty() {
+        eprintln!("Not translated:");
+        for note in &notes {
+            eprintln!("  {note}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use crate::git;
+use crate::package::{self, PACK_DIR, PACK_REPOS_DIR};
+use crate::Result;
+
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use walkdir::WalkDir;
+
+/// Leftovers touched more recently than this may belong to an install which
+/// is still running.
+const PARTIAL_MIN_AGE: Duration = Duration::from_secs(10 * 60);
+
+pub fn exec() -> Result<()> {
+    let start = SystemTime::now();
+    let mut repos = Vec::new();
+    let mut partial = Vec::new();
+
+    let found = RefCell::new(Vec::new());
+    package::walk_packs(&None, false, false, |cate, option, name| {
+        found
+            .borrow_mut()
+            .push((format!("{cate}/{option}/{name}"), PACK_DIR.join(cate).join(option).join(name)));
+    })?;
+    if PACK_REPOS_DIR.is_dir() {
+        for entry in PACK_REPOS_DIR.read_dir()?.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            found.borrow_mut().push((format!("repos/{name}"), entry.path()));
+        }
+    }
+    for (name, path) in found.into_inner() {
+        // Local packages and `rtp` links point to repositories handled elsewhere.
+        if path.symlink_metadata()?.file_type().is_symlink() {
+            continue;
+        }
+        if git::is_partial_clone(&path) || is_empty_dir(&path)? {
+            if is_stale(&path, start) {
+                partial.push((name, path));
+            }
+        } else if path.join(".git").exists() {
+            repos.push((name, path));
+        }
+    }
+    for path in git::mirrors() {
+        repos.push((format!("mirror {}", path.display()), path));
+    }
+
+    let mut total = 0;
+    for (name, path) in partial {
+        let size = dir_size(&path);
+        fs::remove_dir_all(&path)?;
+        println!("{name}: removed partial clone, reclaimed {}", git::format_bytes(size));
+        total += size;
+    }
+    for (name, path) in repos {
+        let before = dir_size(&path);
+        if let Err(e) = git::gc(&path) {
+            println!("{name}: {e}");
+            continue;
+        }
+        let reclaimed = before.saturating_sub(dir_size(&path));
+        println!("{name}: reclaimed {}", git::format_bytes(reclaimed));
+        total += reclaimed;
+    }
+    println!("Total reclaimed {}", git::format_bytes(total));
+    Ok(())
+}
+
+fn is_empty_dir(path: &Path) -> Result<bool> {
+    Ok(path.read_dir()?.next().is_none())
+}
+
+/// Whether nothing below `path` changed for `PARTIAL_MIN_AGE` before `start`.
+fn is_stale(path: &Path, start: SystemTime) -> bool {
+    let newest = WalkDir::new(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.metadata().ok()?.modified().ok())
+        .max();
+    newest.is_some_and(|t| t + PARTIAL_MIN_AGE < start)
+}
+
+/// Sum up the size of all files below `path` without following symlinks.
+fn dir_size(path: &Path) -> usize {
+    WalkDir::new(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| usize::try_from(m.len()).unwrap_or(usize::MAX))
+        .sum()
+}
@@ -0,0 +1,122 @@
+use crate::cli::Import;
+use crate::import::{self, Imported};
+use crate::lock::{self, LockEntry};
+use crate::package::{self, Package};
+use crate::{Error, Result};
+
+use std::fs;
+use std::path::Path;
+
+pub fn exec(args: Import) -> Result<()> {
+    let mut packs = package::fetch()?;
+    let mut added = Vec::new();
+    let mut notes = Vec::new();
+    let mut locked = Vec::new();
+
+    for file in &args.files {
+        let src = fs::read_to_string(file)?;
+        let path = Path::new(file);
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => {
+                let (imported, entries) = import::read_json_lock(&src)
+                    .map_err(|e| Error::PackFile(format!("Fail to parse {file}: {e}")))?;
+                // The lockfile usually comes along with the specs, which
+                // carry more options.
+                let imported = imported
+                    .into_iter()
+                    .filter(|i| !packs.iter().any(|p| p.name == i.package.name))
+                    .collect();
+                add(&mut packs, imported, &args, &mut added, &mut notes);
+                locked.extend(entries);
+            }
+            Some("lua") => add(
+                &mut packs,
+                import::read_lua(&src),
+                &args,
+                &mut added,
+                &mut notes,
+            ),
+            _ => {
+                let (imported, unread) = import::read_vim(&src);
+                notes.extend(unread);
+                add(&mut packs, imported, &args, &mut added, &mut notes);
+            }
+        }
+    }
+
+    if !added.is_empty() {
+        package::save(packs.clone())?;
+        println!("Imported:");
+        for name in &added {
+            println!("  {name}");
+        }
+    }
+    if !locked.is_empty() {
+        lock_commits(&packs, locked, &mut notes)?;
+    }
+    if !notes.is_empty() {
+        println!("Not translated:");
+        for note in &notes {
+            println!("  {note}");
+        }
+    }
+    if !added.is_empty() {
+        println!("Run `npack install` to install the imported packages");
+    }
+    Ok(())
+}
+
+fn add(
+    packs: &mut Vec<Package>,
+    imported: Vec<Imported>,
+    args: &Import,
+    added: &mut Vec<String>,
+    notes: &mut Vec<String>,
+) {
+    for Imported {
+        mut package,
+        skipped,
+    } in imported
+    {
+        if packs.iter().any(|p| p.name == package.name) {
+            notes.push(format!("{}: already in the packfile", package.name));
+            continue;
+        }
+        package.set_category(args.category.as_str());
+        notes.extend(
+            skipped
+                .into_iter()
+                .map(|s| format!("{}: {s}", package.name)),
+        );
+        added.push(package.name.clone());
+        packs.push(package);
+    }
+}
+
+/// Record the commits of a lazy.nvim lockfile in the lockfile, `npack install
+/// --locked` then checks them out. lazy.nvim names plugins after the
+/// repository only.
+fn lock_commits(packs: &[Package], locked: Vec<LockEntry>, notes: &mut Vec<String>) -> Result<()> {
+    let mut entries = lock::fetch()?;
+    let mut count = 0;
+    for entry in locked {
+        let pack = packs.iter().find(|p| {
+            p.name == entry.name || (!p.local && p.name.rsplit('/').next() == Some(&entry.name))
+        });
+        match pack {
+            Some(p) => {
+                entries.retain(|e| e.name != p.name);
+                entries.push(LockEntry {
+                    name: p.name.clone(),
+                    ..entry
+                });
+                count += 1;
+            }
+            None => notes.push(format!("{}: no package for the locked commit", entry.name)),
+        }
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    lock::save(entries)?;
+    println!("Locked {count} package(s), `npack install --locked` checks them out");
+    Ok(())
+}
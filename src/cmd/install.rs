@@ -1,9 +1,16 @@
+use crate::archive;
+use crate::cmd::update;
+use crate::echo;
 use crate::git;
-use crate::package::{self, Package};
+use crate::lock::{self, LockEntry};
+use crate::package::{self, LocalMode, Package};
+use crate::settings::{self, Settings};
 use crate::task::{TaskManager, TaskType};
+use crate::utils;
 use crate::{Error, Result};
 
 use num_cpus;
+use std::fs;
 use std::os::unix::fs::symlink;
 use std::path::Path;
 
@@ -12,12 +19,21 @@ struct Plugins {
     category: String,
     opt: bool,
     on: Option<String>,
-    after_update: Option<String>,
     types: Option<Vec<String>>,
     build: Option<String>,
     threads: usize,
     local: bool,
-    branch: Option<String>
+    copy: bool,
+    branch: Option<String>,
+    tag: Option<String>,
+    commit: Option<String>,
+    version: Option<String>,
+    depth: Option<u32>,
+    submodules: bool,
+    rtp: Option<String>,
+    sha256: Option<String>,
+    locked: bool,
+    offline: bool,
 }
 
 pub fn install_plugins(args: crate::cli::Install) -> Result<()> {
@@ -29,16 +45,25 @@ pub fn install_plugins(args: crate::cli::Install) -> Result<()> {
         .map(|e| e.split(',').map(std::string::ToString::to_string).collect::<Vec<String>>());
 
     let plugins = Plugins {
-        names: args.package,
+        names: args.package.unwrap_or_default(),
         category: args.category,
         opt,
         on: args.on,
-        after_update: args.after_update,
         types,
         build: args.build,
         threads,
         local: args.local,
-        branch: args.branch
+        copy: args.copy,
+        branch: args.branch,
+        tag: args.tag,
+        commit: args.commit,
+        version: args.version,
+        depth: args.depth,
+        submodules: !args.no_submodules,
+        rtp: args.rtp.as_deref().map(package::check_rtp).transpose()?,
+        sha256: args.sha256,
+        locked: args.locked,
+        offline: args.offline,
     };
     let mut packs = package::fetch()?;
     {
@@ -51,12 +76,28 @@ pub fn install_plugins(args: crate::cli::Install) -> Result<()> {
             }
         } else {
             let mut pack = {
-                let mut p = Package::new(&plugins.names, &plugins.category, plugins.opt);
+                let mut p = if archive::is_archive(&plugins.names) {
+                    let name = archive::name_from_location(&plugins.names);
+                    let mut p = Package::new(&name, &plugins.category, plugins.opt);
+                    p.archive = Some(plugins.names.clone());
+                    p.sha256 = plugins.sha256.clone();
+                    p
+                } else if git::is_url(&plugins.names) {
+                    let name = git::name_from_url(&plugins.names);
+                    let mut p = Package::new(&name, &plugins.category, plugins.opt);
+                    p.set_url(&plugins.names);
+                    p
+                } else {
+                    Package::new(&plugins.names, &plugins.category, plugins.opt)
+                };
                 p.local = if Path::new(&plugins.names).is_dir() {
                     true
                 } else {
-                    plugins.local
+                    plugins.local || plugins.copy
                 };
+                if plugins.copy {
+                    p.local_mode = LocalMode::Copy;
+                }
                 if let Some(ref c) = plugins.on {
                     p.set_load_command(c);
                 }
@@ -69,6 +110,20 @@ pub fn install_plugins(args: crate::cli::Install) -> Result<()> {
                 if let Some(ref c) = plugins.branch {
                     p.set_branch(c);
                 }
+                if let Some(ref c) = plugins.tag {
+                    p.set_tag(c);
+                }
+                if let Some(ref c) = plugins.commit {
+                    p.set_commit(c);
+                }
+                if let Some(ref c) = plugins.version {
+                    p.set_version(c);
+                }
+                if let Some(d) = plugins.depth {
+                    p.set_depth(d);
+                }
+                p.submodules.enabled = plugins.submodules;
+                p.rtp = plugins.rtp.clone();
                 p
             };
             let having = match packs.iter_mut().find(|x| x.name == pack.name) {
@@ -81,6 +136,16 @@ pub fn install_plugins(args: crate::cli::Install) -> Result<()> {
                         x.set_opt(pack.opt);
                         x.set_types(pack.for_types.clone());
 
+                        x.url = pack.url.clone();
+                        x.branch = pack.branch.clone();
+                        x.tag = pack.tag.clone();
+                        x.commit = pack.commit.clone();
+                        x.version = pack.version.clone();
+                        x.depth = pack.depth;
+                        x.submodules = pack.submodules.clone();
+                        x.rtp = pack.rtp.clone();
+                        x.archive = pack.archive.clone();
+                        x.sha256 = pack.sha256.clone();
                         x.load_command = pack.load_command.clone();
                         x.build_command = pack.build_command.clone();
                     }
@@ -94,7 +159,15 @@ pub fn install_plugins(args: crate::cli::Install) -> Result<()> {
             manager.add(pack);
         }
 
-        for fail in manager.run(install_plugin)? {
+        let mut settings = settings::fetch()?;
+        settings.offline = plugins.offline;
+        let failures = if plugins.locked {
+            let entries = lock::fetch()?;
+            manager.run(move |p, line| install_plugin(p, line, &settings, Some(&entries)))?
+        } else {
+            manager.run(move |p, line| install_plugin(p, line, &settings, None))?
+        };
+        for fail in failures {
             packs.retain(|e| e.name != fail);
         }
     }
@@ -102,32 +175,107 @@ pub fn install_plugins(args: crate::cli::Install) -> Result<()> {
     packs.sort_by(|a, b| a.name.cmp(&b.name));
 
     package::update_pack_plugin(&packs)?;
+    lock::update(&packs)?;
     package::save(packs)
 }
 
-fn install_plugin(pack: &Package) -> (Result<()>, bool) {
-    let res = do_install(pack);
+pub fn install_plugin(
+    pack: &Package,
+    line: echo::Line,
+    settings: &Settings,
+    locked: Option<&[LockEntry]>,
+) -> (Result<()>, bool) {
+    let entry = locked.and_then(|entries| lock_entry(pack, entries));
+    // Installed packages are updated to the locked commit, a failed update
+    // leaves the checkout in place.
+    let installed = pack.path().is_dir();
+    let res = do_install(pack, line, settings, entry);
     let status = match res {
         Err(Error::PluginInstalled(_)) => true,
+        _ if installed => update::keep_package(&res),
         Err(_) => false,
         Ok(()) => true,
     };
     (res, status)
 }
 
-fn do_install(pack: &Package) -> Result<()> {
+/// The commit `pack` is installed at with `--locked`. Local packages and
+/// packages added after the lockfile was written are installed as usual,
+/// archives are pinned by their `sha256`.
+fn lock_entry<'a>(pack: &Package, entries: &'a [LockEntry]) -> Option<&'a LockEntry> {
+    if pack.local || pack.archive.is_some() {
+        None
+    } else {
+        lock::find(entries, &pack.name)
+    }
+}
+
+fn do_install(
+    pack: &Package,
+    line: echo::Line,
+    settings: &Settings,
+    locked: Option<&LockEntry>,
+) -> Result<()> {
     let path = pack.path();
+    let repo = pack.repo_path();
+    let spec = match locked {
+        Some(entry) => pack.spec(settings).at_commit(&entry.commit, &entry.branch),
+        None => pack.spec(settings),
+    }
+    .with_progress(line);
     if path.is_dir() {
-        Err(Error::plugin_installed(&path))
+        match locked {
+            Some(_) if !pack.local && pack.archive.is_none() => git::update(&spec, &repo, false),
+            _ => Err(Error::plugin_installed(&path)),
+        }
     } else if pack.local {
         let src = Path::new(&pack.name);
         if !src.is_dir() {
             Err(Error::NoPlugin)
+        } else if pack.local_mode == LocalMode::Copy {
+            utils::sync_directory(src, &path)?;
+            Ok(())
         } else {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
             symlink(src, &path)?;
             Ok(())
         }
+    } else if repo != path && repo.is_dir() {
+        pack.link_rtp()
+    } else if let Some(source) = pack.archive_source(settings) {
+        archive::install(&source.with_progress(line), &repo)?;
+        pack.link_rtp()
     } else {
-        git::clone(&pack.name, &path, &pack.branch)
+        git::clone(&spec, &repo)?;
+        pack.link_rtp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locked_mixed_packfile() {
+        let entry = |name: &str| LockEntry {
+            name: name.to_string(),
+            commit: "0123abc".to_string(),
+            branch: None,
+        };
+        let entries = [entry("user/locked"), entry("/tmp/local"), entry("vendor")];
+
+        let locked = Package::new("user/locked", "default", false);
+        let unlocked = Package::new("user/new", "default", false);
+        let mut local = Package::new("/tmp/local", "default", false);
+        local.local = true;
+        let mut archive = Package::new("vendor", "default", false);
+        archive.archive = Some("https://example.com/vendor.tar.gz".to_string());
+
+        assert_eq!(lock_entry(&locked, &entries), Some(&entries[0]));
+        assert_eq!(lock_entry(&unlocked, &entries), None);
+        assert_eq!(lock_entry(&local, &entries), None);
+        assert_eq!(lock_entry(&archive, &entries), None);
     }
 }
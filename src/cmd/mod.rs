@@ -1,7 +1,13 @@
 pub mod config;
+pub mod export;
+pub mod gc;
 pub mod generate;
+pub mod import;
 pub mod install;
 pub mod list;
 pub mod move_cmd;
+pub mod outdated;
+pub mod rollback;
+pub mod snapshot;
 pub mod uninstall;
 pub mod update;
@@ -21,10 +21,15 @@ pub fn move_plugin(args: Move) -> Result<()> {
 
         let path = package::Package::new(&args.package, &args.category, args.opt).path();
         if origin_path != path {
-            utils::copy_directory(&origin_path, &path)?;
-            fs::remove_dir_all(&origin_path)?;
+            if pack.repo_path() != origin_path {
+                fs::remove_file(&origin_path)?;
+            } else {
+                utils::copy_directory(&origin_path, &path)?;
+                fs::remove_dir_all(&origin_path)?;
+            }
             pack.set_category(&args.category);
             pack.set_opt(args.opt);
+            pack.link_rtp()?;
             true
         } else {
             false
@@ -0,0 +1,81 @@
+use crate::cli::Outdated;
+use crate::cmd::update;
+use crate::echo;
+use crate::git::{self, Commit};
+use crate::package::{self, Package};
+use crate::settings::{self, Settings};
+use crate::task::{TaskManager, TaskType};
+use crate::{Error, Result};
+
+use std::sync::{Arc, Mutex};
+
+pub fn exec(args: Outdated) -> Result<()> {
+    let threads = args.threads.unwrap_or_else(num_cpus::get);
+    check_plugins(&args.package, threads, &args.skip)
+}
+
+/// Fetch every package without touching the checkout and report the commits
+/// it is behind.
+pub fn check_plugins(plugins: &[String], threads: usize, skip: &[String]) -> Result<()> {
+    let packs = package::fetch()?;
+
+    let mut manager = TaskManager::new(TaskType::Outdated, threads);
+    if plugins.is_empty() {
+        for pack in &packs {
+            if skip.iter().any(|x| pack.name.contains(x)) {
+                println!("Skip {}", pack.name);
+                continue;
+            }
+            manager.add(pack.clone());
+        }
+    } else {
+        for pack in packs.iter().filter(|x| plugins.contains(&x.name)) {
+            manager.add(pack.clone());
+        }
+    }
+
+    let settings = settings::fetch()?;
+    let pending = Arc::new(Mutex::new(Vec::new()));
+    let results = pending.clone();
+    manager.run(move |pack, line| {
+        let res = do_check(pack, line, &settings).map(|commits| {
+            if commits.is_empty() {
+                line.status("up to date");
+            } else {
+                line.status(&format!("{} behind", commits.len()));
+                let mut r = results.lock().expect("To get access to Lock");
+                r.push((pack.name.clone(), commits));
+            }
+        });
+        let status = matches!(res, Ok(()) | Err(Error::SkipLocal));
+        (res, status)
+    })?;
+
+    let mut pending = pending.lock().expect("To get access to Lock");
+    if pending.is_empty() {
+        println!("All packages are up to date.");
+        return Ok(());
+    }
+    pending.sort_by(|a, b| a.0.cmp(&b.0));
+    for (name, commits) in pending.iter() {
+        print_pending(name, commits);
+    }
+    Ok(())
+}
+
+fn print_pending(name: &str, commits: &[Commit]) {
+    let plural = if commits.len() == 1 { "" } else { "s" };
+    println!("{name}: {} commit{plural} behind", commits.len());
+    update::print_commits(commits);
+}
+
+fn do_check(pack: &Package, line: echo::Line, settings: &Settings) -> Result<Vec<Commit>> {
+    let path = pack.repo_path();
+    if !path.is_dir() {
+        Err(Error::PluginNotInstalled)
+    } else if pack.local || pack.archive.is_some() {
+        Err(Error::SkipLocal)
+    } else {
+        git::pending(&pack.spec(settings).with_progress(line), &path)
+    }
+}
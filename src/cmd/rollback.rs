@@ -0,0 +1,57 @@
+use crate::cli::Rollback;
+use crate::cmd::update::keep_package;
+use crate::git;
+use crate::lock::{self, LockEntry};
+use crate::package::{self, Package};
+use crate::settings::{self, Settings};
+use crate::task::{TaskManager, TaskType};
+use crate::{Error, Result};
+
+pub fn exec(args: Rollback) -> Result<()> {
+    rollback_plugins(&args.package, args.offline, args.force)
+}
+
+fn rollback_plugins(plugins: &[String], offline: bool, force: bool) -> Result<()> {
+    let mut packs = package::fetch()?;
+    let history = lock::fetch_history()?;
+    let mut settings = settings::fetch()?;
+    settings.offline = offline;
+
+    let mut manager = TaskManager::new(TaskType::Update, 1);
+    for pack in packs.iter().filter(|x| plugins.is_empty() || plugins.contains(&x.name)) {
+        if lock::find(&history, &pack.name).is_some() {
+            manager.add(pack.clone());
+        } else if !plugins.is_empty() {
+            println!("No history for {}", pack.name);
+        }
+    }
+
+    let failures = manager.run(move |pack, _| {
+        let res = do_rollback(pack, &settings, &history, force);
+        let status = keep_package(&res);
+        (res, status)
+    })?;
+    lock::update(&packs)?;
+    for fail in failures {
+        packs.retain(|e| e.name != fail);
+    }
+
+    packs.sort_by(|a, b| a.name.cmp(&b.name));
+    package::update_pack_plugin(&packs)
+}
+
+fn do_rollback(
+    pack: &Package,
+    settings: &Settings,
+    history: &[LockEntry],
+    force: bool,
+) -> Result<()> {
+    let path = pack.repo_path();
+    let entry = lock::find(history, &pack.name).ok_or(Error::NotLocked)?;
+    if !path.is_dir() {
+        Err(Error::PluginNotInstalled)
+    } else {
+        let spec = pack.spec(settings).at_commit(&entry.commit, &entry.branch);
+        git::reset(&spec, &path, force)
+    }
+}
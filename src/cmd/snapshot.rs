@@ -0,0 +1,178 @@
+use crate::cli::{Snapshot, SnapshotCommand};
+use crate::cmd::install;
+use crate::lock::{self, LockEntry};
+use crate::package::{self, PACK_CONFIG_DIR, PACK_FILE};
+use crate::settings;
+use crate::task::{TaskManager, TaskType};
+use crate::{Error, Result};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+
+const SNAPSHOT_PACKFILE: &str = "packfile";
+const SNAPSHOT_LOCKFILE: &str = "packfile.lock";
+const CONFIG_EXTENSIONS: [&str; 2] = ["vim", "lua"];
+
+lazy_static! {
+    static ref SNAPSHOT_DIR: PathBuf = (*PACK_CONFIG_DIR).join("snapshots");
+}
+
+pub fn exec(args: Snapshot) -> Result<()> {
+    match args.cmd {
+        SnapshotCommand::Save { name, force } => save(&name, force),
+        SnapshotCommand::List => list(),
+        SnapshotCommand::Restore { name, threads } => {
+            restore(&name, threads.unwrap_or_else(num_cpus::get))
+        }
+    }
+}
+
+fn snapshot_path(name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.starts_with('.') || name.contains('/') {
+        return Err(Error::Snapshot(format!("Invalid snapshot name `{name}`")));
+    }
+    Ok(SNAPSHOT_DIR.join(name))
+}
+
+/// The package config snippets stored directly in `dir`.
+fn config_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+    for entry in (dir.read_dir()?).flatten() {
+        let path = entry.path();
+        let is_config = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| CONFIG_EXTENSIONS.contains(&e));
+        if path.is_file() && is_config {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+fn copy_configs(src: &Path, dst: &Path) -> Result<()> {
+    for file in config_files(src)? {
+        if let Some(name) = file.file_name() {
+            fs::copy(&file, dst.join(name))?;
+        }
+    }
+    Ok(())
+}
+
+fn save(name: &str, force: bool) -> Result<()> {
+    let path = snapshot_path(name)?;
+    if path.exists() {
+        if !force {
+            return Err(Error::Snapshot(format!("Snapshot `{name}` already exists")));
+        }
+        fs::remove_dir_all(&path)?;
+    }
+    fs::create_dir_all(&path)?;
+
+    let packs = package::fetch()?;
+    if PACK_FILE.is_file() {
+        fs::copy(&*PACK_FILE, path.join(SNAPSHOT_PACKFILE))?;
+    }
+    let entries = packs.iter().filter_map(LockEntry::resolve).collect();
+    lock::save_to_lockfile(path.join(SNAPSHOT_LOCKFILE), entries)?;
+    copy_configs(&PACK_CONFIG_DIR, &path)?;
+
+    println!("Saved snapshot `{name}` with {} packages.", packs.len());
+    Ok(())
+}
+
+fn list() -> Result<()> {
+    if !SNAPSHOT_DIR.is_dir() {
+        return Ok(());
+    }
+    let mut names = (SNAPSHOT_DIR.read_dir()?)
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| e.file_name().to_str().map(|s| s.to_string()))
+        .collect::<Vec<String>>();
+    names.sort();
+    for name in names {
+        let entries = lock::fetch_from_lockfile(SNAPSHOT_DIR.join(&name).join(SNAPSHOT_LOCKFILE))
+            .map(|e| e.len())
+            .unwrap_or(0);
+        println!("{name} ({entries} packages)");
+    }
+    Ok(())
+}
+
+fn restore(name: &str, threads: usize) -> Result<()> {
+    let path = snapshot_path(name)?;
+    if !path.is_dir() {
+        return Err(Error::Snapshot(format!("No snapshot named `{name}`")));
+    }
+
+    // Read everything before touching the current config, a broken snapshot
+    // must not leave the user without one.
+    let packfile = path.join(SNAPSHOT_PACKFILE);
+    let mut packs = if packfile.is_file() {
+        package::fetch_from_packfile(&packfile)
+            .map(|(packs, _)| packs)
+            .map_err(|e| Error::Snapshot(format!("Fail to parse snapshot packfile: {e}")))?
+    } else {
+        vec![]
+    };
+    let entries = lock::fetch_from_lockfile(path.join(SNAPSHOT_LOCKFILE))
+        .map_err(|e| Error::Snapshot(format!("Fail to read snapshot lockfile: {e}")))?;
+    let settings = settings::fetch()?;
+
+    replace_configs(&path, &PACK_CONFIG_DIR)?;
+    package::save(packs.clone())?;
+
+    let mut manager = TaskManager::new(TaskType::Install, threads);
+    for pack in packs.iter().filter(|p| !p.local) {
+        manager.add(pack.clone());
+    }
+    let failures = if manager.packages().is_empty() {
+        vec![]
+    } else {
+        manager.run(move |p, line| install::install_plugin(p, line, &settings, Some(&entries)))?
+    };
+    lock::update(&packs)?;
+    for fail in failures {
+        packs.retain(|e| e.name != fail);
+    }
+
+    packs.sort_by(|a, b| a.name.cmp(&b.name));
+    package::update_pack_plugin(&packs)
+}
+
+/// Replace the config snippets in `dst` by the ones in `src`. The new files
+/// are copied next to the old ones first and only renamed over them once all
+/// copies succeeded.
+fn replace_configs(src: &Path, dst: &Path) -> Result<()> {
+    let mut staged = Vec::new();
+    for file in config_files(src)? {
+        if let Some(name) = file.file_name() {
+            let target = dst.join(name);
+            let mut tmp = target.clone().into_os_string();
+            tmp.push(".npack-tmp");
+            let tmp = PathBuf::from(tmp);
+            if let Err(e) = fs::copy(&file, &tmp) {
+                for (tmp, _) in staged {
+                    let _ = fs::remove_file(tmp);
+                }
+                return Err(e.into());
+            }
+            staged.push((tmp, target));
+        }
+    }
+    for file in config_files(dst)? {
+        if !staged.iter().any(|(_, target)| *target == file) {
+            fs::remove_file(file)?;
+        }
+    }
+    for (tmp, target) in staged {
+        fs::rename(tmp, target)?;
+    }
+    Ok(())
+}
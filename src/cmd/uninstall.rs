@@ -24,16 +24,22 @@ fn uninstall_plugins(plugins: &[String], all: bool) -> Result<()> {
 }
 
 fn uninstall_plugin(plugin: &Package, all: bool) -> Result<()> {
-    let config_file = plugin.config_path();
     let plugin_path = plugin.path();
 
-    if config_file.is_file() && all {
-        fs::remove_file(&config_file)?;
+    for config_file in [plugin.config_path(), plugin.lua_config_path()] {
+        if config_file.is_file() && all {
+            fs::remove_file(&config_file)?;
+        }
     }
 
     if plugin_path.is_dir() {
         fs::remove_dir_all(&plugin_path)?;
     }
 
+    let repo_path = plugin.repo_path();
+    if repo_path != plugin_path && repo_path.is_dir() {
+        fs::remove_dir_all(&repo_path)?;
+    }
+
     Ok(())
 }
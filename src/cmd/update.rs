@@ -1,9 +1,19 @@
-use crate::git;
-use crate::package::{self, Package};
+use crate::archive;
+use crate::echo;
+use crate::git::{self, Changelog, Commit};
+use crate::cmd::outdated;
+use crate::lock;
+use crate::package::{self, LocalMode, Package};
+use crate::settings::{self, Settings};
 use crate::task::{TaskManager, TaskType};
+use crate::utils;
 use crate::cli::Update;
 use crate::{Error, Result};
 
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use termion::{color, style};
+
 pub fn exec(args: Update) -> Result<()>{
 
     if args.packfile {
@@ -12,7 +22,11 @@ pub fn exec(args: Update) -> Result<()>{
 
     let threads = args.threads.unwrap_or_else(num_cpus::get);
 
-    update_plugins(&args.package, threads, &args.skip)
+    if args.dry_run {
+        return outdated::check_plugins(&args.package, threads, &args.skip);
+    }
+
+    update_plugins(&args.package, threads, &args.skip, args.force)
 }
 
 fn update_packfile() -> Result<()> {
@@ -25,7 +39,7 @@ fn update_packfile() -> Result<()> {
     Ok(())
 }
 
-fn update_plugins(plugins: &[String], threads: usize, skip: &[String]) -> Result<()> {
+fn update_plugins(plugins: &[String], threads: usize, skip: &[String], force: bool) -> Result<()> {
     let mut packs = package::fetch()?;
 
     let mut manager = TaskManager::new(TaskType::Update, threads);
@@ -43,7 +57,23 @@ fn update_plugins(plugins: &[String], threads: usize, skip: &[String]) -> Result
         }
     }
 
-    for fail in manager.run(update_plugin)? {
+    lock::save_history(manager.packages())?;
+
+    let settings = settings::fetch()?;
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let results = changes.clone();
+    let failures = manager.run(move |pack, line| {
+        let (res, status) = update_plugin(pack, line, &settings, force);
+        let res = res.map(|changelog| {
+            if let Some(c) = changelog {
+                let mut r = results.lock().expect("To get access to Lock");
+                r.push((pack.name.clone(), c));
+            }
+        });
+        (res, status)
+    })?;
+    lock::update(&packs)?;
+    for fail in failures {
         packs.retain(|e| e.name != fail);
     }
 
@@ -51,26 +81,95 @@ fn update_plugins(plugins: &[String], threads: usize, skip: &[String]) -> Result
 
     package::update_pack_plugin(&packs)?;
 
+    let mut changes = changes.lock().expect("To get access to Lock");
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+    for (name, changelog) in changes.iter() {
+        print_changelog(name, changelog);
+    }
+
     Ok(())
 }
 
-fn update_plugin(pack: &Package) -> (Result<()>, bool) {
-    let res = do_update(pack);
-    let status = match res {
-        Err(Error::SkipLocal) | Err(Error::Git(_)) => true,
+fn print_changelog(name: &str, changelog: &Changelog) {
+    println!("{name}: {} -> {}", changelog.from, changelog.to);
+    print_commits(&changelog.commits);
+}
+
+/// Print one line per commit, breaking changes are highlighted.
+pub fn print_commits(commits: &[Commit]) {
+    for c in commits {
+        if c.breaking {
+            println!(
+                "    {} {}{}{}{}{}",
+                c.id,
+                style::Bold,
+                color::Fg(color::Red),
+                c.summary,
+                color::Fg(color::Reset),
+                style::Reset
+            );
+        } else {
+            println!("    {} {}", c.id, c.summary);
+        }
+    }
+}
+
+fn update_plugin(
+    pack: &Package,
+    line: echo::Line,
+    settings: &Settings,
+    force: bool,
+) -> (Result<Option<Changelog>>, bool) {
+    let res = do_update(pack, line, settings, force);
+    let status = keep_package(&res);
+    (res, status)
+}
+
+/// Whether a package stays in the loader after its update or rollback ended
+/// with `res`. Only packages which are gone or broken are dropped.
+pub fn keep_package<T>(res: &Result<T>) -> bool {
+    match res {
+        Err(Error::SkipLocal)
+        | Err(Error::SourceUnavailable(_))
+        | Err(Error::LocalChanges(_))
+        | Err(Error::Git(_))
+        | Err(Error::Auth(_))
+        | Err(Error::Timeout(_))
+        | Err(Error::Signature(_))
+        | Err(Error::NoTrustedKeys)
+        | Err(Error::Archive(_))
+        | Err(Error::Checksum(_, _))
+        | Err(Error::NotCached(_)) => true,
         Err(_) => false,
         _ => true,
-    };
-    (res, status)
+    }
 }
 
-fn do_update(pack: &Package) -> Result<()> {
-    let path = pack.path();
+fn do_update(
+    pack: &Package,
+    line: echo::Line,
+    settings: &Settings,
+    force: bool,
+) -> Result<Option<Changelog>> {
+    let path = pack.repo_path();
     if !path.is_dir() {
         Err(Error::PluginNotInstalled)
+    } else if pack.local && pack.local_mode == LocalMode::Copy {
+        let src = Path::new(&pack.name);
+        if !src.is_dir() {
+            return Err(Error::SourceUnavailable(pack.name.clone()));
+        }
+        utils::sync_directory(src, &path)?;
+        Ok(None)
     } else if pack.local {
         Err(Error::SkipLocal)
+    } else if let Some(source) = pack.archive_source(settings) {
+        archive::update(&source.with_progress(line), &path)?;
+        pack.link_rtp()?;
+        Ok(None)
     } else {
-        git::update(&pack.name, &path)
+        let (old, _) = git::head(&path)?;
+        git::update(&pack.spec(settings).with_progress(line), &path, force)?;
+        git::changelog(&path, &old)
     }
 }
@@ -58,6 +58,20 @@ pub fn message(line: u16, offset: u16, msg: &str) {
     async_print(line, offset + msg.len() as u16, msg);
 }
 
+/// The place on a task line where the status of the task is shown.
+#[derive(Debug, Clone, Copy)]
+pub struct Line {
+    pub line: u16,
+    pub offset: u16,
+}
+
+impl Line {
+    /// Replace the status shown on the line.
+    pub fn status(&self, msg: &str) {
+        inline_message(self.line, self.offset, msg);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -13,7 +13,27 @@ pub enum Error {
     #[error("Format")]
     Format,
     #[error("Git {0}")]
-    Git(#[from] git2::Error),
+    Git(git2::Error),
+    #[error("Authentication failed: {0}")]
+    Auth(String),
+    #[error("Timed out after {0}s")]
+    Timeout(u64),
+    #[error("No cached mirror of {0}")]
+    NotCached(String),
+    #[error("Subdirectory {0} not found in the repository")]
+    NoSubdir(String),
+    #[error("Subdirectory {0} has to be a relative path inside the repository")]
+    InvalidSubdir(String),
+    #[error("git gc failed: {0}")]
+    Gc(String),
+    #[error("No valid signature on {0}")]
+    Signature(String),
+    #[error("Set `gpg_home` or `allowed_signers` in the settings to verify signatures")]
+    NoTrustedKeys,
+    #[error("Archive error: {0}")]
+    Archive(String),
+    #[error("Checksum mismatch, expected {0} but got {1}")]
+    Checksum(String, String),
     #[error("")]
     Editor,
     #[error("Fail to build plugin: {0}")]
@@ -24,9 +44,21 @@ pub enum Error {
     NoPlugin,
     #[error("SkipLocal")]
     SkipLocal,
+    #[error("Source {0} is not available, the copy is kept")]
+    SourceUnavailable(String),
+    #[error("Skipped, {0} (use --force to overwrite)")]
+    LocalChanges(String),
     #[error("{0}")]
     PluginInstalled(String),
-    #[error("")]
+    #[error("Plugin is not in the lockfile")]
+    NotLocked,
+    #[error("Invalid version requirement: {0}")]
+    Version(String),
+    #[error("No tag matches version {0}")]
+    NoMatchingVersion(String),
+    #[error("{0}")]
+    Snapshot(String),
+    #[error("{0}")]
     PackFile(String),
     #[error("Fail to copy directory: {0}")]
     CopyDir(#[from] std::path::StripPrefixError),
@@ -40,6 +72,17 @@ pub enum Error {
     TaskError(#[from] crate::task::TaskError),
 }
 
+impl From<git2::Error> for Error {
+    fn from(e: git2::Error) -> Error {
+        let ssh_auth = e.class() == git2::ErrorClass::Ssh && e.message().contains("authenticat");
+        if e.code() == git2::ErrorCode::Auth || ssh_auth {
+            Error::Auth(e.message().to_string())
+        } else {
+            Error::Git(e)
+        }
+    }
+}
+
 impl Error {
     pub fn build<T: AsRef<str>>(s: T) -> Error {
         Error::Build(s.as_ref().to_string())
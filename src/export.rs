@@ -0,0 +1,482 @@
+//! Writers for the plugin lists of other plugin managers.
+
+use crate::cli::ExportFormat;
+use crate::lock::{self, LockEntry};
+use crate::package::{lua_str, Package};
+
+enum Field {
+    Str(String),
+    List(Vec<String>),
+    Bool(bool),
+    /// Lua expression written as is
+    Raw(String),
+}
+
+/// Write the spec of `packs` for the plugin manager `format`. Commits recorded
+/// in `locked` are used for packages not pinned to a commit.
+///
+/// Returns the spec and the options which could not be translated.
+pub fn export(
+    format: ExportFormat,
+    packs: &[Package],
+    locked: &[LockEntry],
+) -> (String, Vec<String>) {
+    if format == ExportFormat::NvimPackLock {
+        return nvim_pack_lock(packs, locked);
+    }
+    let mut specs = Vec::new();
+    let mut notes = Vec::new();
+    for pack in packs {
+        let commit = pack
+            .commit
+            .clone()
+            .or_else(|| lock::find(locked, &pack.name).map(|e| e.commit.clone()));
+        let mut note = |what: &str| notes.push(format!("{}: {what}", pack.name));
+        if pack.archive.is_some() {
+            note("archives are not supported");
+            continue;
+        }
+        let spec = match format {
+            ExportFormat::VimPlug => vim_plug(pack, commit, &mut note),
+            ExportFormat::Lazy => lazy(pack, commit, &mut note),
+            ExportFormat::Packer => packer(pack, commit, &mut note),
+            ExportFormat::VimPack => vim_pack(pack, commit, &mut note),
+            ExportFormat::NvimPackLock => unreachable!(),
+        };
+        if let Some(spec) = spec {
+            specs.push(spec);
+        }
+    }
+
+    let out = match format {
+        ExportFormat::VimPlug => {
+            format!(
+                "call plug#begin()\n{}call plug#end()\n",
+                lines(&specs, "", "")
+            )
+        }
+        ExportFormat::Lazy => format!("return {{\n{}}}\n", lines(&specs, "  ", ",")),
+        ExportFormat::Packer => format!(
+            "return require('packer').startup(function(use)\n{}end)\n",
+            lines(&specs, "  ", "")
+        ),
+        ExportFormat::VimPack => format!("vim.pack.add({{\n{}}})\n", lines(&specs, "  ", ",")),
+        ExportFormat::NvimPackLock => unreachable!(),
+    };
+    (out, notes)
+}
+
+fn lines(specs: &[String], indent: &str, end: &str) -> String {
+    specs
+        .iter()
+        .map(|s| format!("{indent}{s}{end}\n"))
+        .collect()
+}
+
+/// The pin of a package as npack resolves it, a commit takes precedence over a
+/// tag and a tag over a version.
+fn pin_fields(
+    pack: &Package,
+    commit: Option<String>,
+    fields: &mut Vec<(&'static str, Field)>,
+    version: Option<&dyn Fn(&str) -> Field>,
+    note: &mut dyn FnMut(&str),
+) {
+    if let Some(ref b) = pack.branch {
+        fields.push(("branch", Field::Str(b.clone())));
+    }
+    if let Some(c) = commit {
+        fields.push(("commit", Field::Str(c)));
+    } else if let Some(ref t) = pack.tag {
+        fields.push(("tag", Field::Str(t.clone())));
+    } else if let Some(ref v) = pack.version {
+        match version {
+            Some(version) => fields.push(("version", version(v))),
+            None => note(&format!("version: {v} (semver ranges are not supported)")),
+        }
+    }
+}
+
+/// Options no other manager has.
+fn common_notes(pack: &Package, note: &mut dyn FnMut(&str)) {
+    if pack.verify {
+        note("verify: signature");
+    }
+}
+
+fn vim_plug(pack: &Package, commit: Option<String>, note: &mut dyn FnMut(&str)) -> Option<String> {
+    let mut fields = Vec::new();
+    if let Some(ref c) = pack.load_command {
+        fields.push(("on", Field::Str(c.clone())));
+    }
+    if !pack.for_types.is_empty() {
+        fields.push(("for", Field::List(pack.for_types.clone())));
+    }
+    if pack.opt && fields.is_empty() {
+        fields.push(("on", Field::List(vec![])));
+    }
+    if let Some(ref b) = pack.build_command {
+        fields.push(("do", Field::Str(b.clone())));
+    }
+    pin_fields(pack, commit, &mut fields, None, note);
+    if let Some(ref r) = pack.rtp {
+        fields.push(("rtp", Field::Str(r.clone())));
+    }
+    if !pack.submodules.enabled {
+        note("submodules are always checked out");
+    }
+    common_notes(pack, note);
+
+    let mut spec = format!("Plug {}", vim_str(&source(pack)));
+    if !fields.is_empty() {
+        let dict = fields
+            .into_iter()
+            .map(|(k, v)| format!("{}: {}", vim_str(k), vim_value(v)))
+            .collect::<Vec<String>>();
+        spec.push_str(&format!(", {{ {} }}", dict.join(", ")));
+    }
+    Some(spec)
+}
+
+fn lazy(pack: &Package, commit: Option<String>, note: &mut dyn FnMut(&str)) -> Option<String> {
+    let mut fields = Vec::new();
+    if pack.local {
+        fields.push(("dir", Field::Str(pack.name.clone())));
+    } else if pack.url.is_some() {
+        fields.push(("url", Field::Str(pack.remote_url())));
+    }
+    if let Some(ref c) = pack.load_command {
+        fields.push(("cmd", Field::Str(c.clone())));
+    }
+    if !pack.for_types.is_empty() {
+        fields.push(("ft", Field::List(pack.for_types.clone())));
+    }
+    if pack.opt && pack.load_command.is_none() && pack.for_types.is_empty() {
+        fields.push(("lazy", Field::Bool(true)));
+    }
+    if let Some(ref b) = pack.build_command {
+        fields.push(("build", Field::Str(b.clone())));
+    }
+    pin_fields(
+        pack,
+        commit,
+        &mut fields,
+        Some(&|v| Field::Str(v.to_string())),
+        note,
+    );
+    if !pack.submodules.enabled {
+        fields.push(("submodules", Field::Bool(false)));
+    }
+    if let Some(ref r) = pack.rtp {
+        note(&format!("rtp: {r}"));
+    }
+    common_notes(pack, note);
+
+    let short = !pack.local && pack.url.is_none();
+    Some(lua_table(short.then_some(pack.name.as_str()), fields))
+}
+
+fn packer(pack: &Package, commit: Option<String>, note: &mut dyn FnMut(&str)) -> Option<String> {
+    let mut fields = Vec::new();
+    if let Some(ref c) = pack.load_command {
+        fields.push(("cmd", Field::Str(c.clone())));
+    }
+    if !pack.for_types.is_empty() {
+        fields.push(("ft", Field::List(pack.for_types.clone())));
+    }
+    if pack.opt && pack.load_command.is_none() && pack.for_types.is_empty() {
+        fields.push(("opt", Field::Bool(true)));
+    }
+    if let Some(ref b) = pack.build_command {
+        fields.push(("run", Field::Str(b.clone())));
+    }
+    pin_fields(pack, commit, &mut fields, None, note);
+    if let Some(ref r) = pack.rtp {
+        fields.push(("rtp", Field::Str(r.clone())));
+    }
+    if !pack.submodules.enabled {
+        note("submodules are always checked out");
+    }
+    common_notes(pack, note);
+
+    let source = source(pack);
+    if fields.is_empty() {
+        Some(format!("use {}", lua_str(&source)))
+    } else {
+        Some(format!("use {}", lua_table(Some(&source), fields)))
+    }
+}
+
+fn vim_pack(pack: &Package, commit: Option<String>, note: &mut dyn FnMut(&str)) -> Option<String> {
+    if pack.local {
+        note("local packages are not supported");
+        return None;
+    }
+    let mut fields = vec![("src", Field::Str(pack.remote_url()))];
+    let mut pin = Vec::new();
+    let range = |v: &str| Field::Raw(format!("vim.version.range({})", lua_str(v)));
+    pin_fields(pack, commit, &mut pin, Some(&range), note);
+    // vim.pack takes a single version, the branch only if nothing else is pinned.
+    if let Some((_, version)) = pin.into_iter().last() {
+        fields.push(("version", version));
+    }
+    if let Some(ref c) = pack.load_command {
+        note(&format!("on: {c} (every plugin is loaded on startup)"));
+    }
+    if !pack.for_types.is_empty() {
+        note(&format!(
+            "for: {} (every plugin is loaded on startup)",
+            pack.for_types.join(", ")
+        ));
+    } else if pack.opt && pack.load_command.is_none() {
+        note("opt (every plugin is loaded on startup)");
+    }
+    if let Some(ref b) = pack.build_command {
+        note(&format!(
+            "build: {b} (run it from a PackChanged autocommand)"
+        ));
+    }
+    if let Some(ref r) = pack.rtp {
+        note(&format!("rtp: {r}"));
+    }
+    common_notes(pack, note);
+    Some(lua_table(None, fields))
+}
+
+/// Write a vim.pack `nvim-pack-lock.json`. vim.pack checks out the recorded
+/// revisions instead of installing the newest commits.
+fn nvim_pack_lock(packs: &[Package], locked: &[LockEntry]) -> (String, Vec<String>) {
+    let mut notes = Vec::new();
+    let mut plugins = Vec::new();
+    for pack in packs {
+        if pack.local || pack.archive.is_some() {
+            notes.push(format!("{}: only git packages are supported", pack.name));
+            continue;
+        }
+        let locked = lock::find(locked, &pack.name).map(|e| e.commit.clone());
+        let rev = match pack.commit.clone().or(locked) {
+            Some(rev) => rev,
+            None => {
+                notes.push(format!("{}: no commit in the lockfile", pack.name));
+                continue;
+            }
+        };
+        let mut fields = vec![("rev", rev), ("src", pack.remote_url())];
+        // The lockfile quotes string versions, ranges are written as is.
+        let mut pin = Vec::new();
+        let range = |v: &str| Field::Raw(v.to_string());
+        pin_fields(
+            pack,
+            pack.commit.clone(),
+            &mut pin,
+            Some(&range),
+            &mut |_| {},
+        );
+        match pin.pop() {
+            Some((_, Field::Str(v))) => fields.push(("version", format!("'{v}'"))),
+            Some((_, Field::Raw(v))) => fields.push(("version", v)),
+            _ => {}
+        }
+        // vim.pack keys its plugins by directory name only.
+        let name = pack.name.rsplit('/').next().unwrap_or(&pack.name);
+        if let Some((_, other, _)) = plugins.iter().find(|(n, _, _)| n == name) {
+            notes.push(format!("{}: `{name}` is already taken by {other}", pack.name));
+            continue;
+        }
+        let fields = fields
+            .iter()
+            .map(|(k, v)| format!("      {}: {}", json_str(k), json_str(v)))
+            .collect::<Vec<String>>();
+        plugins.push((name.to_string(), pack.name.clone(), fields.join(",\n")));
+    }
+    plugins.sort();
+
+    let plugins = plugins
+        .iter()
+        .map(|(name, _, fields)| format!("    {}: {{\n{fields}\n    }}", json_str(name)))
+        .collect::<Vec<String>>();
+    let out = if plugins.is_empty() {
+        "{\n  \"plugins\": {}\n}\n".to_string()
+    } else {
+        format!("{{\n  \"plugins\": {{\n{}\n  }}\n}}\n", plugins.join(",\n"))
+    };
+    (out, notes)
+}
+
+fn json_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// The name or url a manager installs the package from.
+fn source(pack: &Package) -> String {
+    if pack.url.is_some() {
+        pack.remote_url()
+    } else {
+        pack.name.clone()
+    }
+}
+
+fn lua_table(first: Option<&str>, fields: Vec<(&str, Field)>) -> String {
+    let items = first
+        .map(lua_str)
+        .into_iter()
+        .chain(
+            fields
+                .into_iter()
+                .map(|(k, v)| format!("{k} = {}", lua_value(v))),
+        )
+        .collect::<Vec<String>>();
+    format!("{{ {} }}", items.join(", "))
+}
+
+fn lua_value(value: Field) -> String {
+    match value {
+        Field::Str(s) => lua_str(&s),
+        Field::List(l) => format!(
+            "{{ {} }}",
+            l.iter()
+                .map(|s| lua_str(s))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        Field::Bool(b) => b.to_string(),
+        Field::Raw(r) => r,
+    }
+}
+
+fn vim_str(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+fn vim_value(value: Field) -> String {
+    match value {
+        Field::Str(s) | Field::Raw(s) => vim_str(&s),
+        Field::List(l) => format!(
+            "[{}]",
+            l.iter()
+                .map(|s| vim_str(s))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        Field::Bool(b) => (b as u8).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packs() -> Vec<Package> {
+        let mut tree = Package::new("scrooloose/nerdtree", "default", true);
+        tree.set_load_command("NERDTreeToggle");
+        let mut fzf = Package::new("junegunn/fzf", "default", false);
+        fzf.set_build_command("./install --all");
+        fzf.set_version("^0.40");
+        let mut other = Package::new("me/tool", "default", false);
+        other.set_url("https://git.example.com/me/tool.git");
+        other.set_branch("main");
+        vec![tree, fzf, other]
+    }
+
+    #[test]
+    fn export_vim_plug() {
+        let (out, notes) = export(ExportFormat::VimPlug, &packs(), &[]);
+        assert_eq!(
+            out,
+            "call plug#begin()
+Plug 'scrooloose/nerdtree', { 'on': 'NERDTreeToggle' }
+Plug 'junegunn/fzf', { 'do': './install --all' }
+Plug 'https://git.example.com/me/tool.git', { 'branch': 'main' }
+call plug#end()
+"
+        );
+        assert_eq!(
+            notes,
+            ["junegunn/fzf: version: ^0.40 (semver ranges are not supported)"]
+        );
+    }
+
+    #[test]
+    fn export_lazy_and_vim_pack() {
+        let locked = [LockEntry {
+            name: "me/tool".to_string(),
+            commit: "0123abc".to_string(),
+            branch: None,
+        }];
+        let (out, _) = export(ExportFormat::Lazy, &packs(), &locked);
+        assert_eq!(
+            out,
+            "return {
+  { 'scrooloose/nerdtree', cmd = 'NERDTreeToggle' },
+  { 'junegunn/fzf', build = './install --all', version = '^0.40' },
+  { url = 'https://git.example.com/me/tool.git', branch = 'main', commit = '0123abc' },
+}
+"
+        );
+        let (out, notes) = export(ExportFormat::VimPack, &packs(), &locked);
+        assert_eq!(
+            out,
+            "vim.pack.add({
+  { src = 'https://github.com/scrooloose/nerdtree' },
+  { src = 'https://github.com/junegunn/fzf', version = vim.version.range('^0.40') },
+  { src = 'https://git.example.com/me/tool.git', version = '0123abc' },
+})
+"
+        );
+        assert_eq!(notes.len(), 2);
+    }
+
+    #[test]
+    fn export_nvim_pack_lock() {
+        let locked = [LockEntry {
+            name: "junegunn/fzf".to_string(),
+            commit: "0123abc".to_string(),
+            branch: None,
+        }];
+        let (out, notes) = export(ExportFormat::NvimPackLock, &packs(), &locked);
+        assert_eq!(
+            out,
+            r#"{
+  "plugins": {
+    "fzf": {
+      "rev": "0123abc",
+      "src": "https://github.com/junegunn/fzf",
+      "version": "^0.40"
+    }
+  }
+}
+"#
+        );
+        assert_eq!(notes.len(), 2);
+    }
+
+    #[test]
+    fn export_nvim_pack_lock_name_collision() {
+        let packs = [
+            Package::new("a/nvim", "default", false),
+            Package::new("b/nvim", "default", false),
+        ];
+        let locked = packs
+            .iter()
+            .map(|p| LockEntry {
+                name: p.name.clone(),
+                commit: "0123abc".to_string(),
+                branch: None,
+            })
+            .collect::<Vec<LockEntry>>();
+        let (out, notes) = export(ExportFormat::NvimPackLock, &packs, &locked);
+        assert_eq!(out.matches("\"nvim\"").count(), 1);
+        assert!(out.contains("github.com/a/nvim"));
+        assert_eq!(notes, ["b/nvim: `nvim` is already taken by a/nvim"]);
+    }
+}
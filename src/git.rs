@@ -1,95 +1,1079 @@
-use crate::Result;
+use crate::echo;
+use crate::{Error, Result};
 
 use git2::{self, Repository};
+use lazy_static::lazy_static;
+use semver::{Version, VersionReq};
+use std::convert::TryFrom;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 const LOCATION: &str = "https://github.com";
+const MIRROR_REFSPECS: [&str; 2] = ["+refs/heads/*:refs/heads/*", "+refs/tags/*:refs/tags/*"];
 
-fn github_url(name: &str) -> String {
+lazy_static! {
+    /// Bare mirrors shared by all config roots of a user.
+    static ref MIRROR_DIR: Option<PathBuf> = dirs::cache_dir().map(|d| d.join("npack"));
+}
+
+/// Everything needed to clone, fetch and check out a package.
+#[derive(Debug, Clone)]
+pub struct Spec {
+    pub url: String,
+    /// Urls tried in order when `url` fails
+    pub fallback_urls: Vec<String>,
+    /// Branch to clone instead of the remote HEAD
+    pub branch: Option<String>,
+    pub pin: Pin,
+    /// Limit the fetched history to this many commits
+    pub depth: Option<u32>,
+    /// Private keys tried after the ssh-agent
+    pub ssh_keys: Vec<PathBuf>,
+    pub submodules: Submodules,
+    /// Where transfer progress is reported
+    pub progress: Option<echo::Line>,
+    /// How often a failed network operation is repeated
+    pub retries: u32,
+    /// Delay before the first retry, doubled for every further retry
+    pub retry_delay: Duration,
+    /// Cancel a network operation running longer than this
+    pub timeout: Option<Duration>,
+    /// Go through a shared bare mirror of the remote
+    pub cache: bool,
+    /// Only use the mirrors, never the network
+    pub offline: bool,
+    /// Require a valid signature on the checked out commit or tag
+    pub verify: Option<Verify>,
+}
+
+/// Where trusted signing keys are looked up.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Verify {
+    /// GnuPG home holding the trusted keyring
+    pub gpg_home: Option<PathBuf>,
+    /// Allowed signers file for SSH signatures
+    pub allowed_signers: Option<PathBuf>,
+}
+
+/// Which submodules of a repository are checked out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Submodules {
+    pub enabled: bool,
+    /// Only these submodules, all if empty
+    pub include: Vec<String>,
+    /// Never these submodules
+    pub exclude: Vec<String>,
+}
+
+impl Default for Submodules {
+    fn default() -> Submodules {
+        Submodules {
+            enabled: true,
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+impl Submodules {
+    pub fn allows(&self, name: &str) -> bool {
+        self.enabled
+            && (self.include.is_empty() || self.include.iter().any(|n| n == name))
+            && !self.exclude.iter().any(|n| n == name)
+    }
+}
+
+impl Spec {
+    pub fn new<T: Into<String>>(url: T) -> Spec {
+        Spec {
+            url: url.into(),
+            fallback_urls: Vec::new(),
+            branch: None,
+            pin: Pin::Head,
+            depth: None,
+            ssh_keys: Vec::new(),
+            submodules: Submodules::default(),
+            progress: None,
+            retries: 0,
+            retry_delay: Duration::from_secs(1),
+            timeout: None,
+            cache: false,
+            offline: false,
+            verify: None,
+        }
+    }
+
+    /// A copy of this spec checking out `commit` on `branch`.
+    pub fn at_commit(&self, commit: &str, branch: &Option<String>) -> Spec {
+        Spec {
+            branch: branch.clone().or_else(|| self.branch.clone()),
+            pin: Pin::Commit(commit.to_string()),
+            ..self.clone()
+        }
+    }
+
+    /// A copy of this spec reporting transfer progress on `line`.
+    pub fn with_progress(self, line: echo::Line) -> Spec {
+        Spec {
+            progress: Some(line),
+            ..self
+        }
+    }
+
+    /// One spec per remote url, the primary url first.
+    fn remotes(&self) -> Vec<Spec> {
+        std::iter::once(&self.url)
+            .chain(self.fallback_urls.iter())
+            .map(|url| Spec {
+                url: url.clone(),
+                fallback_urls: Vec::new(),
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    fn is_local(&self) -> bool {
+        self.url.starts_with("file://") || !is_url(&self.url)
+    }
+
+    fn callbacks(&self) -> git2::RemoteCallbacks<'static> {
+        let mut callbacks = credentials(self.ssh_keys.clone());
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        if self.progress.is_some() || deadline.is_some() {
+            report_progress(&mut callbacks, self.progress, deadline);
+        }
+        callbacks
+    }
+
+    fn fetch_options(&self) -> git2::FetchOptions<'static> {
+        if let Some(timeout) = self.timeout {
+            set_server_timeout(timeout);
+        }
+        let mut opts = git2::FetchOptions::new();
+        opts.download_tags(git2::AutotagOption::All)
+            .update_fetchhead(true)
+            .remote_callbacks(self.callbacks());
+        // A pinned commit is likely outside of a shallow history and the
+        // local transport can't fetch shallow.
+        let full = matches!(self.pin, Pin::Commit(_)) || self.is_local();
+        if let (Some(depth), false) = (self.depth, full) {
+            opts.depth(i32::try_from(depth).unwrap_or(i32::MAX));
+        }
+        opts
+    }
+}
+
+/// Answer credential requests, each source is tried once: the ssh-agent, the
+/// configured key files, the git credential helpers and the default
+/// credentials. ssh urls without a user are asked for one first.
+fn credentials(ssh_keys: Vec<PathBuf>) -> git2::RemoteCallbacks<'static> {
+    let mut agent_tried = false;
+    let mut keys = ssh_keys.into_iter();
+    let mut helper_tried = false;
+    let mut default_tried = false;
+
+    let mut callbacks = git2::RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| {
+        let user = username.unwrap_or("git");
+        if allowed.contains(git2::CredentialType::USERNAME) {
+            return git2::Cred::username(user);
+        }
+        if allowed.contains(git2::CredentialType::SSH_KEY) {
+            if !agent_tried {
+                agent_tried = true;
+                if let Ok(cred) = git2::Cred::ssh_key_from_agent(user) {
+                    return Ok(cred);
+                }
+            }
+            if let Some(key) = keys.next() {
+                return git2::Cred::ssh_key(user, None, &key, None);
+            }
+        }
+        if allowed.contains(git2::CredentialType::USER_PASS_PLAINTEXT) && !helper_tried {
+            helper_tried = true;
+            let config = git2::Config::open_default()?;
+            if let Ok(cred) = git2::Cred::credential_helper(&config, url, username) {
+                return Ok(cred);
+            }
+        }
+        if allowed.contains(git2::CredentialType::DEFAULT) && !default_tried {
+            default_tried = true;
+            return git2::Cred::default();
+        }
+        Err(git2::Error::new(
+            git2::ErrorCode::Auth,
+            git2::ErrorClass::Callback,
+            format!("no valid credentials for {url}"),
+        ))
+    });
+    callbacks
+}
+
+/// Minimal time between two progress reports.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+/// Longest remote message shown on a task line.
+const MAX_REMOTE_MESSAGE: usize = 40;
+
+/// Show received objects and remote messages on `line`. The transfer is
+/// cancelled once `deadline` has passed.
+fn report_progress(
+    callbacks: &mut git2::RemoteCallbacks<'static>,
+    line: Option<echo::Line>,
+    deadline: Option<Instant>,
+) {
+    let in_time = move || deadline.is_none_or(|d| Instant::now() < d);
+    let mut last = Instant::now();
+    callbacks.transfer_progress(move |stats| {
+        let done = stats.received_objects() == stats.total_objects()
+            && stats.indexed_deltas() == stats.total_deltas();
+        if let (Some(line), true) = (line, last.elapsed() >= PROGRESS_INTERVAL || done) {
+            last = Instant::now();
+            line.status(&format_progress(&stats));
+        }
+        in_time()
+    });
+    callbacks.sideband_progress(move |data| {
+        let line = match line {
+            Some(line) => line,
+            None => return in_time(),
+        };
+        let text = String::from_utf8_lossy(data);
+        let msg = text
+            .split(['\r', '\n'])
+            .map(str::trim)
+            .rfind(|l| !l.is_empty());
+        if let Some(msg) = msg {
+            let msg = msg.chars().take(MAX_REMOTE_MESSAGE).collect::<String>();
+            line.status(&format!("remote: {msg}"));
+        }
+        in_time()
+    });
+}
+
+/// Let libgit2 give up on connections which stall without any progress.
+///
+/// The timeouts are process wide, only the first call takes effect.
+fn set_server_timeout(timeout: Duration) {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        let millis = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+        // Safety: `Once` makes sure no other thread changes the options at the
+        // same time and no network operation has started yet.
+        unsafe {
+            let _ = git2::opts::set_server_connect_timeout_in_milliseconds(millis);
+            let _ = git2::opts::set_server_timeout_in_milliseconds(millis);
+        }
+    });
+}
+
+lazy_static! {
+    /// Urls which only succeeded after retrying.
+    static ref RETRIED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+}
+
+/// Take the urls which needed retries since the last call.
+pub fn take_retried() -> Vec<String> {
+    let mut retried = RETRIED.lock().expect("To get access to Lock");
+    std::mem::take(&mut *retried)
+}
+
+/// Check whether a failure is worth another attempt.
+fn is_transient(e: &Error) -> bool {
+    match e {
+        Error::Timeout(_) => true,
+        Error::Git(e) => matches!(
+            e.class(),
+            git2::ErrorClass::Net | git2::ErrorClass::Http | git2::ErrorClass::Ssh | git2::ErrorClass::Os
+        ),
+        _ => false,
+    }
+}
+
+/// Run `action` and repeat it with exponential backoff on transient failures.
+fn retry<T, F>(spec: &Spec, mut action: F) -> Result<T>
+where
+    F: FnMut() -> Result<T>,
+{
+    let mut delay = spec.retry_delay;
+    let mut attempt = 0;
+    loop {
+        let started = Instant::now();
+        let err = match action() {
+            Ok(v) => {
+                if attempt > 0 {
+                    RETRIED.lock().expect("To get access to Lock").push(spec.url.clone());
+                }
+                return Ok(v);
+            }
+            // The deadline cancels a transfer from the progress callbacks, a
+            // stalled connect or read is given up by libgit2 itself.
+            Err(Error::Git(e)) => match spec.timeout {
+                Some(t) if e.code() == git2::ErrorCode::Timeout || started.elapsed() >= t => {
+                    Error::Timeout(t.as_secs())
+                }
+                _ => Error::Git(e),
+            },
+            Err(e) => e,
+        };
+        if attempt >= spec.retries || !is_transient(&err) {
+            return Err(err);
+        }
+        attempt += 1;
+        if let Some(line) = spec.progress {
+            line.status(&format!("retrying in {}s ({attempt}/{}): {err}", delay.as_secs(), spec.retries));
+        }
+        thread::sleep(delay);
+        delay *= 2;
+    }
+}
+
+fn format_progress(stats: &git2::Progress) -> String {
+    let percent = |done: usize, total: usize| (done * 100).checked_div(total).unwrap_or(100);
+    if stats.received_objects() < stats.total_objects() {
+        format!(
+            "receiving {}/{} objects ({}%) {}",
+            stats.received_objects(),
+            stats.total_objects(),
+            percent(stats.received_objects(), stats.total_objects()),
+            format_bytes(stats.received_bytes())
+        )
+    } else {
+        format!(
+            "resolving {}/{} deltas ({}%) {}",
+            stats.indexed_deltas(),
+            stats.total_deltas(),
+            percent(stats.indexed_deltas(), stats.total_deltas()),
+            format_bytes(stats.received_bytes())
+        )
+    }
+}
+
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// The revision a checkout is moved to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pin {
+    /// Follow the fetched HEAD of the branch
+    Head,
+    Tag(String),
+    Commit(String),
+    /// Newest tag matching a semver range
+    Version(String),
+}
+
+pub fn github_url(name: &str) -> String {
     format!("{LOCATION}/{name}")
 }
 
-fn fetch(repo: &Repository, name: &str) -> Result<()> {
-    let url = github_url(name);
+/// Check whether `s` is a remote url rather than a `user/repo` shorthand.
+pub fn is_url(s: &str) -> bool {
+    s.contains("://") || (s.contains('@') && s.contains(':'))
+}
+
+/// Derive a package name from a remote url.
+///
+/// The name is made of the last two path components of the url without a
+/// trailing `.git`, e.g. `https://gitlab.com/user/repo.git` becomes `user/repo`.
+pub fn name_from_url(url: &str) -> String {
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest,
+        None => url.rsplit_once(':').map_or(url, |(_, rest)| rest),
+    };
+    let path = path.trim_end_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let mut parts = path.rsplitn(3, '/').take(2).collect::<Vec<&str>>();
+    parts.reverse();
+    parts.join("/")
+}
 
-    let mut opts = git2::FetchOptions::new();
-    opts.download_tags(git2::AutotagOption::All)
-        .update_fetchhead(true);
+/// Reference the upstream branch is fetched to when the checkout must not move.
+const UPSTREAM_REF: &str = "refs/npack/upstream";
+/// Reference to the commit npack checked out last.
+const SYNCED_REF: &str = "refs/npack/synced";
+/// Namespace the remote branches are fetched to. The local branch only moves
+/// once the new commit passed the signature check.
+const FETCHED_REFS: &str = "refs/npack/heads/";
 
-    let refspec = "refs/heads/*:refs/heads/*";
-    let mut remote = repo.remote_anonymous(&url)?;
-    remote.fetch(&[refspec], Some(&mut opts), None)?;
-    Ok(())
+/// A commit pulled in by an update.
+#[derive(Debug, Clone)]
+pub struct Commit {
+    /// Abbreviated commit id
+    pub id: String,
+    /// First line of the commit message
+    pub summary: String,
+    /// The message announces a breaking change
+    pub breaking: bool,
+}
+
+/// Marker used anywhere in a commit message to announce a breaking change.
+const BREAKING_MARKER: &str = "BREAKING";
+
+/// Whether a commit message announces a breaking change, by the marker or by
+/// a conventional commit subject like `feat(api)!: ...`.
+fn is_breaking(message: &str) -> bool {
+    if message.contains(BREAKING_MARKER) {
+        return true;
+    }
+    let subject = message.lines().next().unwrap_or("");
+    let prefix = match subject.split_once("!:") {
+        Some((prefix, _)) => prefix,
+        None => return false,
+    };
+    let kind = match prefix.split_once('(') {
+        Some((kind, scope)) => match scope.strip_suffix(')') {
+            Some(scope) if !scope.contains(['(', ')']) => kind,
+            _ => return false,
+        },
+        None => prefix,
+    };
+    !kind.is_empty() && kind.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// The commits between two states of a repository.
+#[derive(Debug, Clone)]
+pub struct Changelog {
+    /// Abbreviated id of the old HEAD
+    pub from: String,
+    /// Abbreviated id of the new HEAD
+    pub to: String,
+    /// Commits pulled in, newest first
+    pub commits: Vec<Commit>,
+}
+
+fn fetch(repo: &Repository, spec: &Spec) -> Result<()> {
+    fetch_refspec(repo, spec, &format!("+refs/heads/*:{FETCHED_REFS}*"))
+}
+
+/// The fetched counterpart of the checked out branch, `HEAD` if it was never
+/// fetched or nothing is checked out.
+fn fetched_head(repo: &Repository) -> Result<String> {
+    let head = repo.head()?;
+    if let (true, Some(branch)) = (head.is_branch(), head.shorthand()) {
+        let fetched = format!("{FETCHED_REFS}{branch}");
+        if repo.refname_to_id(&fetched).is_ok() {
+            return Ok(fetched);
+        }
+    }
+    Ok("HEAD".to_string())
+}
+
+fn fetch_refspec(repo: &Repository, spec: &Spec, refspec: &str) -> Result<()> {
+    let source = via_mirror(spec)?;
+    first_success(&source, |remote| {
+        let mut opts = remote.fetch_options();
+        repo.remote_anonymous(&remote.url)?
+            .fetch(&[refspec], Some(&mut opts), None)?;
+        Ok(())
+    })
+}
+
+/// Where the mirror of `url` is kept, urls of the same repository over
+/// different transports share one mirror.
+fn mirror_path(url: &str) -> Option<PathBuf> {
+    let rest = url.split_once("://").map_or(url, |(_, r)| r);
+    let rest = rest.split_once('@').map_or(rest, |(_, r)| r);
+    let rest = rest.trim_end_matches('/');
+    let rest = rest.strip_suffix(".git").unwrap_or(rest);
+    let mut path = MIRROR_DIR.clone()?;
+    for part in rest.split(&['/', ':'][..]) {
+        if !matches!(part, "" | "." | "..") {
+            path.push(part);
+        }
+    }
+    path.set_extension("git");
+    Some(path)
+}
+
+/// The spec to clone or fetch from instead of `spec`.
+///
+/// Refreshes the mirror of the remote unless offline. Local and shallow
+/// packages are used as they are, unless offline.
+fn via_mirror(spec: &Spec) -> Result<Spec> {
+    let use_mirror = spec.offline || (spec.cache && spec.depth.is_none());
+    if spec.is_local() || !use_mirror {
+        return Ok(spec.clone());
+    }
+    let path = mirror_path(&spec.url).ok_or_else(|| Error::NotCached(spec.url.clone()))?;
+    if spec.offline {
+        if !path.is_dir() {
+            return Err(Error::NotCached(spec.url.clone()));
+        }
+    } else {
+        refresh_mirror(spec, &path)?;
+    }
+    Ok(Spec {
+        url: path.to_string_lossy().into_owned(),
+        fallback_urls: Vec::new(),
+        depth: None,
+        ..spec.clone()
+    })
 }
 
-fn sync_repo(repo: &Repository, name: &str) -> Result<()> {
-    fetch(repo, name)?;
-    let reference = "HEAD";
-    let oid = repo.refname_to_id(reference)?;
-    let object = repo.find_object(oid, None)?;
+/// Create or update the bare mirror of `spec` at `path`.
+fn refresh_mirror(spec: &Spec, path: &Path) -> Result<()> {
+    let repo = match Repository::open_bare(path) {
+        Ok(repo) => repo,
+        Err(_) => {
+            fs::create_dir_all(path)?;
+            Repository::init_bare(path)?
+        }
+    };
+    first_success(spec, |remote| {
+        let mut opts = remote.fetch_options();
+        let mut origin = repo.remote_anonymous(&remote.url)?;
+        origin.fetch(&MIRROR_REFSPECS, Some(&mut opts), None)?;
+        // Clones of the mirror check out the default branch of the remote.
+        if let Some(head) = origin.default_branch().ok().as_ref().and_then(|b| b.as_str()) {
+            repo.set_head(head)?;
+        }
+        Ok(())
+    })
+}
+
+/// Run `action` against every remote of `spec` until one succeeds.
+///
+/// Returns the error of the last remote if all fail.
+fn first_success<T, F>(spec: &Spec, mut action: F) -> Result<T>
+where
+    F: FnMut(&Spec) -> Result<T>,
+{
+    let mut last_err = None;
+    for remote in spec.remotes() {
+        match retry(&remote, || action(&remote)) {
+            Ok(v) => return Ok(v),
+            Err(e) => {
+                log::info!("{} failed: {e}", &remote.url);
+                last_err = Some(e);
+            }
+        }
+    }
+    Err(last_err.expect("spec to have at least one url"))
+}
+
+fn sync_repo(repo: &Repository, spec: &Spec) -> Result<()> {
+    fetch(repo, spec)?;
+    checkout(repo, &branch_or_tag(spec, |r| repo.refname_to_id(r).is_ok()))
+}
+
+/// Some managers, like vim.pack, don't tell branches and tags apart. A
+/// `branch` which only exists as a tag is checked out as that tag, `exists`
+/// looks up the fetched references.
+fn branch_or_tag<F: Fn(&str) -> bool>(spec: &Spec, exists: F) -> Spec {
+    let branch = match spec.branch {
+        Some(ref b) => b,
+        None => return spec.clone(),
+    };
+    let is_branch = exists(&format!("refs/heads/{branch}"))
+        || exists(&format!("{FETCHED_REFS}{branch}"));
+    if is_branch || !exists(&format!("refs/tags/{branch}")) {
+        return spec.clone();
+    }
+    let pin = match spec.pin {
+        Pin::Head => Pin::Tag(branch.clone()),
+        ref pin => pin.clone(),
+    };
+    Spec {
+        branch: None,
+        pin,
+        ..spec.clone()
+    }
+}
+
+/// `branch_or_tag` against the references of the remote, before cloning.
+fn remote_branch_or_tag(spec: &Spec) -> Result<Spec> {
+    if spec.branch.is_none() {
+        return Ok(spec.clone());
+    }
+    let mut origin = git2::Remote::create_detached(spec.url.as_str())?;
+    let connection = origin.connect_auth(git2::Direction::Fetch, Some(spec.callbacks()), None)?;
+    let refs = connection
+        .list()?
+        .iter()
+        .map(|head| head.name().to_string())
+        .collect::<Vec<String>>();
+    Ok(branch_or_tag(spec, |r| refs.iter().any(|name| name == r)))
+}
+
+/// Find the newest tag whose name, without a leading `v`, matches `req`.
+fn find_version_tag(repo: &Repository, req: &str) -> Result<String> {
+    let version_req = VersionReq::parse(req).map_err(|_| Error::Version(req.to_string()))?;
+    let tags = repo.tag_names(None)?;
+    tags.iter()
+        .flatten()
+        .filter_map(|t| {
+            let version = Version::parse(t.strip_prefix('v').unwrap_or(t)).ok()?;
+            Some((version, t))
+        })
+        .filter(|(v, _)| version_req.matches(v))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, t)| t.to_string())
+        .ok_or_else(|| Error::NoMatchingVersion(req.to_string()))
+}
+
+/// Find the commit `pin` refers to, `head` is used for `Pin::Head`.
+fn resolve<'r>(repo: &'r Repository, pin: &Pin, head: &str) -> Result<git2::Object<'r>> {
+    let object = match pin {
+        Pin::Head => {
+            let oid = repo.refname_to_id(head)?;
+            repo.find_object(oid, None)?
+        }
+        Pin::Commit(c) => repo.revparse_single(c)?,
+        Pin::Tag(t) => repo.revparse_single(&format!("refs/tags/{t}"))?,
+        Pin::Version(req) => {
+            let tag = find_version_tag(repo, req)?;
+            repo.revparse_single(&format!("refs/tags/{tag}"))?
+        }
+    };
+    Ok(object.peel(git2::ObjectType::Commit)?)
+}
+
+/// Hard reset the current branch to the revision described by `spec.pin`.
+fn checkout(repo: &Repository, spec: &Spec) -> Result<()> {
+    let object = resolve(repo, &spec.pin, &fetched_head(repo)?)?;
+    verify_signature(repo, spec, object.id())?;
     repo.reset(&object, git2::ResetType::Hard, None)?;
-    update_submodules(repo)?;
+    repo.reference(SYNCED_REF, object.id(), true, "npack checkout")?;
+    update_submodules(repo, spec)?;
+    Ok(())
+}
+
+/// Check the signature of the tag `spec.pin` refers to or of `commit`.
+///
+/// libgit2 can't verify signatures, the git executable is used.
+fn verify_signature(repo: &Repository, spec: &Spec, commit: git2::Oid) -> Result<()> {
+    let verify = match spec.verify {
+        Some(ref v) => v,
+        None => return Ok(()),
+    };
+    // Without either git falls back to the default keyring and trusts any
+    // key in it.
+    if verify.gpg_home.is_none() && verify.allowed_signers.is_none() {
+        return Err(Error::NoTrustedKeys);
+    }
+    let (action, target) = match spec.pin {
+        Pin::Tag(ref t) => ("verify-tag", t.clone()),
+        Pin::Version(ref req) => ("verify-tag", find_version_tag(repo, req)?),
+        Pin::Head | Pin::Commit(_) => ("verify-commit", commit.to_string()),
+    };
+    let mut command = process::Command::new("git");
+    if let Some(ref signers) = verify.allowed_signers {
+        command
+            .arg("-c")
+            .arg(format!("gpg.ssh.allowedSignersFile={}", signers.display()));
+    }
+    if let Some(ref home) = verify.gpg_home {
+        command.env("GNUPGHOME", home);
+    }
+    let output = command
+        .arg("--git-dir")
+        .arg(repo.path())
+        .args([action, &target])
+        .output()?;
+    if !output.status.success() {
+        let reason = String::from_utf8_lossy(&output.stderr);
+        let reason = reason.lines().last().unwrap_or("no signature");
+        return Err(Error::Signature(format!("{target} ({reason})")));
+    }
     Ok(())
 }
 
-pub fn clone_recursive(url: &str, path: &Path, branch: &Option<String>) -> Result<git2::Repository> {
+fn submodule_options(spec: &Spec) -> git2::SubmoduleUpdateOptions<'static> {
+    let mut opts = git2::SubmoduleUpdateOptions::new();
+    opts.fetch(spec.fetch_options());
+    opts
+}
+
+pub fn clone_recursive(spec: &Spec, path: &Path) -> Result<git2::Repository> {
     let mut builder = git2::build::RepoBuilder::new();
-    if let Some(branch_name) = branch {
+    if let Some(ref branch_name) = spec.branch {
         builder.branch(branch_name);
     }
-    let repo = builder.clone(url, path.as_ref())?;
-    
-    // Initialize submodules recursively (inlined)
-    fn init_submodules_recursive(repo: &Repository) -> Result<()> {
-        for mut submodule in repo.submodules()? {
-            submodule.init(false)?;
-            
-            submodule.update(true, None)?;
-            
-            // Recursively handle nested submodules
-            if let Ok(sub_repo) = submodule.open() {
-                init_submodules_recursive(&sub_repo)?;
+    builder.fetch_options(spec.fetch_options());
+    let repo = builder.clone(&spec.url, path.as_ref())?;
+    update_submodules(&repo, spec)?;
+    Ok(repo)
+}
+
+pub fn clone(spec: &Spec, target: &Path) -> Result<()> {
+    let source = via_mirror(spec)?;
+    first_success(&source, |remote| {
+        let remote = &remote_branch_or_tag(remote)?;
+        let result = clone_recursive(remote, target).and_then(|repo| {
+            match remote.pin {
+                Pin::Head => {
+                    let head = repo.head()?.peel_to_commit()?.id();
+                    verify_signature(&repo, remote, head)?;
+                }
+                // The pinned revision might not be reachable from the cloned branch.
+                _ => sync_repo(&repo, remote)?,
+            }
+            let head = repo.head()?.peel_to_commit()?.id();
+            repo.reference(SYNCED_REF, head, true, "npack clone")?;
+            Ok(())
+        });
+        if let Err(e) = result {
+            if target.exists() {
+                fs::remove_dir_all(target)?;
             }
+            return Err(e);
         }
-        
         Ok(())
+    })?;
+    if source.url != spec.url {
+        Repository::open(target)?.remote_set_url("origin", &spec.url)?;
     }
-    init_submodules_recursive(&repo)?;
-    Ok(repo)
+    Ok(())
+}
+
+/// Fetch and move the checkout to `spec.pin`.
+///
+/// Refuses to touch a checkout with uncommitted changes or commits that were
+/// never fetched. With `force` those are stashed or kept on a backup branch.
+pub fn update<P: AsRef<Path>>(spec: &Spec, path: P, force: bool) -> Result<()> {
+    let mut repo = Repository::open(&path)?;
+    keep_local_work(&mut repo, force)?;
+    sync_repo(&repo, spec)
+}
+
+/// Refuse to touch a checkout with uncommitted changes or local commits, with
+/// `force` the changes are stashed and the commits kept on a backup branch.
+fn keep_local_work(repo: &mut Repository, force: bool) -> Result<()> {
+    let dirty = is_dirty(repo)?;
+    let local = local_commits(repo)?;
+    if !force {
+        if dirty {
+            return Err(Error::LocalChanges("uncommitted changes".to_string()));
+        }
+        if local > 0 {
+            let plural = if local == 1 { "" } else { "s" };
+            return Err(Error::LocalChanges(format!("{local} local commit{plural}")));
+        }
+    }
+    if local > 0 {
+        backup_head(repo)?;
+    }
+    if dirty {
+        let signature = repo
+            .signature()
+            .or_else(|_| git2::Signature::now("npack", "npack@localhost"))?;
+        repo.stash_save(&signature, "npack update", None)?;
+    }
+    Ok(())
+}
+
+/// Check for modified tracked files, untracked files survive a reset.
+fn is_dirty(repo: &Repository) -> Result<bool> {
+    let mut opts = git2::StatusOptions::new();
+    opts.include_untracked(false)
+        .include_ignored(false)
+        .exclude_submodules(true);
+    Ok(!repo.statuses(Some(&mut opts))?.is_empty())
 }
 
-pub fn clone(name: &str, target: &Path, branch: &Option<String>) -> Result<()> {
-    let url = github_url(name);
-    let result = clone_recursive(&url, target, branch);
-    if let Err(e) = result {
-        fs::remove_dir_all(&target)?;
-        return Err(e.into());
+/// Count the commits on HEAD which are not part of anything fetched before.
+fn local_commits(repo: &Repository) -> Result<usize> {
+    let mut walk = repo.revwalk()?;
+    walk.push_head()?;
+    for glob in ["refs/remotes/*", "refs/tags/*", "refs/npack/*"] {
+        walk.hide_glob(glob)?;
+    }
+    let mut fetched = Vec::new();
+    // FETCH_HEAD is missing in fresh clones.
+    let _ = repo.fetchhead_foreach(|_, _, oid, _| {
+        fetched.push(*oid);
+        true
+    });
+    for oid in fetched {
+        walk.hide(oid)?;
+    }
+    Ok(walk.count())
+}
+
+/// Keep HEAD reachable through a `npack-backup-<timestamp>` branch.
+fn backup_head(repo: &Repository) -> Result<()> {
+    let commit = repo.head()?.peel_to_commit()?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    repo.branch(&format!("npack-backup-{now}"), &commit, false)?;
+    Ok(())
+}
+
+/// List the commits an update would pull in, newest first.
+///
+/// Only the upstream branch is fetched, the checkout and the local branches
+/// are left untouched.
+pub fn pending<P: AsRef<Path>>(spec: &Spec, path: P) -> Result<Vec<Commit>> {
+    let repo = Repository::open(&path)?;
+    let head = repo.head()?;
+    let branch = match head.shorthand() {
+        Some(b) if head.is_branch() => format!("refs/heads/{b}"),
+        _ => "HEAD".to_string(),
+    };
+    fetch_refspec(&repo, spec, &format!("+{branch}:{UPSTREAM_REF}"))?;
+    let target = resolve(&repo, &spec.pin, UPSTREAM_REF)?;
+    let current = head.peel_to_commit()?.id();
+    commits_between(&repo, current, target.id())
+}
+
+/// List the commits reachable from `to` but not from `from`, newest first.
+fn commits_between(repo: &Repository, from: git2::Oid, to: git2::Oid) -> Result<Vec<Commit>> {
+    let mut walk = repo.revwalk()?;
+    walk.push(to)?;
+    walk.hide(from)?;
+    let mut commits = Vec::new();
+    for oid in walk {
+        let commit = repo.find_commit(oid?)?;
+        let message = commit.message().unwrap_or("");
+        commits.push(Commit {
+            id: short_id(commit.as_object())?,
+            summary: commit.summary().unwrap_or("").to_string(),
+            breaking: is_breaking(message),
+        });
+    }
+    Ok(commits)
+}
+
+fn short_id(object: &git2::Object) -> Result<String> {
+    Ok(object.short_id()?.as_str().unwrap_or("").to_string())
+}
+
+/// Describe how HEAD moved since it pointed to `from`.
+///
+/// Returns `None` if HEAD still points to `from`.
+pub fn changelog<P: AsRef<Path>>(path: P, from: &str) -> Result<Option<Changelog>> {
+    let repo = Repository::open(&path)?;
+    let old = repo.revparse_single(from)?;
+    let new = repo.head()?.peel_to_commit()?;
+    if old.id() == new.id() {
+        return Ok(None);
+    }
+    Ok(Some(Changelog {
+        from: short_id(&old)?,
+        to: short_id(new.as_object())?,
+        commits: commits_between(&repo, old.id(), new.id())?,
+    }))
+}
+
+/// Move an existing checkout to `spec.pin`, fetching only if the revision
+/// is missing. Local work is handled as in `update`.
+pub fn reset<P: AsRef<Path>>(spec: &Spec, path: P, force: bool) -> Result<()> {
+    let mut repo = Repository::open(&path)?;
+    keep_local_work(&mut repo, force)?;
+    if resolve(&repo, &spec.pin, "HEAD").is_err() {
+        fetch(&repo, spec)?;
+    }
+    checkout(&repo, spec)
+}
+
+/// Pack and prune the objects of the repository at `path`.
+///
+/// libgit2 has no garbage collection, the git executable is used.
+pub fn gc<P: AsRef<Path>>(path: P) -> Result<()> {
+    let output = process::Command::new("git")
+        .args(["gc", "--prune=now", "--quiet"])
+        .current_dir(path)
+        .output()?;
+    if !output.status.success() {
+        return Err(Error::Gc(String::from_utf8_lossy(&output.stderr).trim().to_string()));
     }
     Ok(())
 }
 
-pub fn update<P: AsRef<Path>>(name: &str, path: P) -> Result<()> {
+/// Check for the remains of a clone which never got to check out a commit.
+pub fn is_partial_clone<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    if !path.join(".git").exists() {
+        return false;
+    }
+    Repository::open(path)
+        .and_then(|repo| repo.head().and_then(|h| h.peel_to_commit()).map(|_| ()))
+        .is_err()
+}
+
+/// List the bare mirrors in the cache.
+pub fn mirrors() -> Vec<PathBuf> {
+    let dir = match *MIRROR_DIR {
+        Some(ref d) if d.is_dir() => d,
+        _ => return Vec::new(),
+    };
+    let mut mirrors = Vec::new();
+    let mut walk = walkdir::WalkDir::new(dir).into_iter();
+    loop {
+        let entry = match walk.next() {
+            None => break,
+            Some(Err(_)) => continue,
+            Some(Ok(e)) => e,
+        };
+        if entry.file_type().is_dir() && entry.path().extension().is_some_and(|x| x == "git") {
+            mirrors.push(entry.into_path());
+            walk.skip_current_dir();
+        }
+    }
+    mirrors
+}
+
+/// Returns the commit id HEAD points to and the name of the checked out branch.
+pub fn head<P: AsRef<Path>>(path: P) -> Result<(String, Option<String>)> {
     let repo = Repository::open(&path)?;
-    sync_repo(&repo, name)
+    let head = repo.head()?;
+    let commit = head.peel_to_commit()?.id().to_string();
+    let branch = if head.is_branch() {
+        head.shorthand().map(|s| s.to_string())
+    } else {
+        None
+    };
+    Ok((commit, branch))
+}
+
+/// Clone or fetch a submodule through the mirror of its url.
+fn update_submodule(repo: &Repository, subm: &mut git2::Submodule, spec: &Spec) -> Result<()> {
+    let url = subm.url().unwrap_or("").to_string();
+    let source = via_mirror(&Spec {
+        url: url.clone(),
+        fallback_urls: Vec::new(),
+        pin: Pin::Head,
+        ..spec.clone()
+    })?;
+    if source.url == url {
+        subm.update(true, Some(&mut submodule_options(spec)))?;
+        return Ok(());
+    }
+    subm.init(false)?;
+    let key = format!("submodule.{}.url", subm.name().unwrap_or(""));
+    let mut config = repo.config()?;
+    config.set_str(&key, &source.url)?;
+    let res = subm.update(false, Some(&mut submodule_options(&source)));
+    config.set_str(&key, &url)?;
+    res?;
+    subm.open()?.remote_set_url("origin", &url)?;
+    Ok(())
 }
 
-fn update_submodules(repo: &Repository) -> Result<()> {
-    fn add_subrepos(repo: &Repository, list: &mut Vec<Repository>) -> Result<()> {
+/// Initialize and update the submodules allowed by `spec.submodules`,
+/// including nested ones.
+fn update_submodules(repo: &Repository, spec: &Spec) -> Result<()> {
+    fn add_subrepos(repo: &Repository, spec: &Spec, list: &mut Vec<Repository>) -> Result<()> {
         for mut subm in repo.submodules()? {
-            if let Some("docs") = subm.name() {
+            if !spec.submodules.allows(subm.name().unwrap_or("")) {
                 continue;
             }
-            subm.update(true, None)?;
+            update_submodule(repo, &mut subm, spec)?;
             list.push(subm.open()?);
         }
         Ok(())
     }
 
     let mut repos = Vec::new();
-    add_subrepos(repo, &mut repos)?;
+    add_subrepos(repo, spec, &mut repos)?;
     while let Some(r) = repos.pop() {
-        add_subrepos(&r, &mut repos)?;
+        add_subrepos(&r, spec, &mut repos)?;
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_formatting() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MiB");
+    }
+
+    #[test]
+    fn submodule_filter() {
+        let all = Submodules::default();
+        assert!(all.allows("docs"));
+
+        let none = Submodules {
+            enabled: false,
+            ..Submodules::default()
+        };
+        assert!(!none.allows("docs"));
+
+        let some = Submodules {
+            enabled: true,
+            include: vec!["lib".to_string(), "docs".to_string()],
+            exclude: vec!["docs".to_string()],
+        };
+        assert!(some.allows("lib"));
+        assert!(!some.allows("docs"));
+        assert!(!some.allows("fixtures"));
+    }
+
+    #[test]
+    fn branch_names_a_tag() {
+        let mut spec = Spec::new("https://example.com/repo");
+        spec.branch = Some("v2.1.0".to_string());
+        let refs = ["refs/heads/main", "refs/tags/v2.1.0"];
+        let spec = branch_or_tag(&spec, |r| refs.contains(&r));
+        assert_eq!(spec.branch, None);
+        assert!(matches!(spec.pin, Pin::Tag(ref t) if t == "v2.1.0"));
+
+        let mut spec = Spec::new("https://example.com/repo");
+        spec.branch = Some("main".to_string());
+        let spec = branch_or_tag(&spec, |r| refs.contains(&r));
+        assert_eq!(spec.branch.as_deref(), Some("main"));
+        assert!(matches!(spec.pin, Pin::Head));
+    }
+
+    #[test]
+    fn breaking_markers() {
+        assert!(is_breaking("feat!: drop vim 7 support"));
+        assert!(is_breaking("refactor(api)!: rename options"));
+        assert!(is_breaking("Remove option\n\nBREAKING CHANGE: gone"));
+        assert!(!is_breaking("fix: handle empty buffers"));
+        assert!(!is_breaking("fix: handle empty buffers\n\nif x!: y"));
+        assert!(!is_breaking("Say hello!: with a bang"));
+    }
+
+    #[test]
+    fn url_detection() {
+        assert!(is_url("https://gitlab.com/user/repo"));
+        assert!(is_url("git@codeberg.org:user/repo.git"));
+        assert!(is_url("file:///srv/git/repo"));
+        assert!(!is_url("user/repo"));
+    }
+
+    #[test]
+    fn mirror_paths() {
+        let base = MIRROR_DIR.clone().unwrap();
+        let expected = base.join("github.com/user/repo.git");
+        assert_eq!(mirror_path("https://github.com/user/repo").unwrap(), expected);
+        assert_eq!(mirror_path("https://github.com/user/repo.git/").unwrap(), expected);
+        assert_eq!(mirror_path("git@github.com:user/repo.git").unwrap(), expected);
+        assert_eq!(
+            mirror_path("ssh://git@host/../x").unwrap(),
+            base.join("host/x.git")
+        );
+    }
+
+    #[test]
+    fn name_from_urls() {
+        assert_eq!(name_from_url("https://gitlab.com/user/repo.git"), "user/repo");
+        assert_eq!(name_from_url("git@codeberg.org:user/repo.git"), "user/repo");
+        assert_eq!(name_from_url("https://git.sr.ht/~user/repo/"), "~user/repo");
+        assert_eq!(name_from_url("file:///srv/git/group/repo"), "group/repo");
+    }
+}
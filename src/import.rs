@@ -0,0 +1,962 @@
+//! Readers for the plugin lists of other plugin managers.
+//!
+//! Only literal values are understood: strings, numbers, booleans, Lua tables
+//! and Vimscript lists and dictionaries. Anything else, like functions or
+//! expressions, is reported as not translated.
+
+use crate::git;
+use crate::lock::LockEntry;
+use crate::package::{self, Package};
+use crate::settings;
+use crate::{Error, Result};
+
+use yaml_rust::{Yaml, YamlLoader};
+
+/// A package read from another plugin manager.
+#[derive(Debug, Clone)]
+pub struct Imported {
+    pub package: Package,
+    /// Options which have no equivalent in npack
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Manager {
+    Plug,
+    Dein,
+    Packer,
+    Lazy,
+    VimPack,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Str(String),
+    Num(String),
+    Ident(String),
+    Sym(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Str(String),
+    Num(String),
+    Bool(bool),
+    /// Lua table, Vimscript list or dictionary
+    Table(Vec<Value>, Vec<(String, Value)>),
+    /// `vim.version.range()` of a vim.pack spec
+    Range(String),
+    /// Anything which is not a literal
+    Opaque,
+}
+
+impl Value {
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// A string or a list of strings.
+    fn strings(&self) -> Option<Vec<String>> {
+        match self {
+            Value::Str(s) => Some(vec![s.clone()]),
+            Value::Table(list, map) if map.is_empty() => list
+                .iter()
+                .map(|v| v.as_str().map(|s| s.to_string()))
+                .collect(),
+            _ => None,
+        }
+    }
+
+    fn truthy(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            Value::Num(n) => Some(n != "0"),
+            _ => None,
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Table(_, map) => map.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Value::Str(s) | Value::Num(s) | Value::Range(s) => s.clone(),
+            Value::Bool(b) => b.to_string(),
+            v => match v.strings() {
+                Some(list) => list.join(", "),
+                None => "...".to_string(),
+            },
+        }
+    }
+}
+
+const SYMBOLS: [&str; 22] = [
+    "...", "..", "==", "~=", "<=", ">=", "{", "}", "[", "]", "(", ")", ",", ";", "=", ":", ".",
+    "+", "-", "*", "#", "!",
+];
+
+/// Split Lua or Vimscript source into tokens. Comments are only recognized
+/// in Lua, Vimscript lines are cut before they are tokenized.
+fn tokenize(src: &str, lua: bool) -> Vec<Token> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let rest: String = chars[i..chars.len().min(i + 4)].iter().collect();
+        if c.is_whitespace() {
+            i += 1;
+        } else if lua && rest.starts_with("--") {
+            i += 2;
+            match long_bracket(&chars, i) {
+                Some((_, end)) => i = end,
+                None => {
+                    while i < chars.len() && chars[i] != '\n' {
+                        i += 1;
+                    }
+                }
+            }
+        } else if lua && c == '[' && long_bracket(&chars, i).is_some() {
+            let (s, end) = long_bracket(&chars, i).unwrap_or_default();
+            tokens.push(Token::Str(s.trim_start_matches('\n').to_string()));
+            i = end;
+        } else if c == '\'' || c == '"' {
+            let (s, end) = quoted(&chars, i, lua);
+            tokens.push(Token::Str(s));
+            i = end;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token::Num(chars[start..i].iter().collect()));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '#')
+            {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else {
+            let sym = SYMBOLS
+                .iter()
+                .find(|s| rest.starts_with(*s))
+                .copied()
+                .unwrap_or("?");
+            i += sym.len().max(1);
+            tokens.push(Token::Sym(sym));
+        }
+    }
+    tokens
+}
+
+/// Read a Lua long bracket `[[...]]` or `[==[...]==]` starting at `start`.
+/// Returns the content and the index after the closing bracket.
+fn long_bracket(chars: &[char], start: usize) -> Option<(String, usize)> {
+    if chars.get(start) != Some(&'[') {
+        return None;
+    }
+    let mut i = start + 1;
+    while chars.get(i) == Some(&'=') {
+        i += 1;
+    }
+    if chars.get(i) != Some(&'[') {
+        return None;
+    }
+    let level = i - start - 1;
+    let close: Vec<char> = std::iter::once(']')
+        .chain(std::iter::repeat_n('=', level))
+        .chain(std::iter::once(']'))
+        .collect();
+    let body = i + 1;
+    let end = (body..=chars.len().saturating_sub(close.len()))
+        .find(|&j| chars[j..].starts_with(&close))
+        .unwrap_or(chars.len());
+    let content = chars[body..end].iter().collect();
+    Some((content, (end + close.len()).min(chars.len())))
+}
+
+/// Read a quoted string starting at `start`. Vimscript single quoted strings
+/// have no escapes besides a doubled quote.
+fn quoted(chars: &[char], start: usize, lua: bool) -> (String, usize) {
+    let quote = chars[start];
+    let literal = !lua && quote == '\'';
+    let mut s = String::new();
+    let mut i = start + 1;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == quote {
+            if literal && chars.get(i + 1) == Some(&quote) {
+                s.push(quote);
+                i += 2;
+                continue;
+            }
+            return (s, i + 1);
+        } else if c == '\\' && !literal && i + 1 < chars.len() {
+            s.push(match chars[i + 1] {
+                'n' => '\n',
+                't' => '\t',
+                other => other,
+            });
+            i += 2;
+        } else if c == '\n' {
+            break;
+        } else {
+            s.push(c);
+            i += 1;
+        }
+    }
+    (s, i)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Parser {
+        Parser { tokens, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, sym: &str) -> bool {
+        if self.peek() == Some(&Token::Sym(sym_of(sym))) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn at_separator(&self) -> bool {
+        matches!(
+            self.peek(),
+            None | Some(Token::Sym(","))
+                | Some(Token::Sym(";"))
+                | Some(Token::Sym("}"))
+                | Some(Token::Sym("]"))
+                | Some(Token::Sym(")"))
+        )
+    }
+
+    /// Read a value inside a table or argument list, expressions like
+    /// `a .. b` are opaque.
+    fn value(&mut self) -> Value {
+        let value = self.literal();
+        if self.at_separator() {
+            value
+        } else {
+            self.skip_expression();
+            Value::Opaque
+        }
+    }
+
+    fn literal(&mut self) -> Value {
+        match self.next() {
+            Some(Token::Str(s)) => Value::Str(s),
+            Some(Token::Num(n)) => Value::Num(n),
+            Some(Token::Ident(i)) => match i.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "function" => {
+                    self.skip_block();
+                    Value::Opaque
+                }
+                "vim" => self.version_range(),
+                _ => Value::Opaque,
+            },
+            Some(Token::Sym("{")) => self.table("}"),
+            Some(Token::Sym("[")) => self.table("]"),
+            _ => Value::Opaque,
+        }
+    }
+
+    /// Check whether the next tokens are the identifiers and symbols `expected`.
+    fn looking_at(&self, expected: &[&str]) -> bool {
+        expected
+            .iter()
+            .enumerate()
+            .all(|(i, e)| match self.peek_at(i) {
+                Some(Token::Sym(s)) => s == e,
+                Some(Token::Ident(s)) => s == e,
+                _ => false,
+            })
+    }
+
+    /// Read `.version.range('...')` after `vim`.
+    fn version_range(&mut self) -> Value {
+        let call = self.looking_at(&[".", "version", ".", "range", "("]);
+        match (call, self.peek_at(5).cloned(), self.peek_at(6)) {
+            (true, Some(Token::Str(range)), Some(Token::Sym(")"))) => {
+                self.pos += 7;
+                Value::Range(range)
+            }
+            _ => Value::Opaque,
+        }
+    }
+
+    /// Read the elements of a table, list or dictionary up to `close`.
+    fn table(&mut self, close: &'static str) -> Value {
+        let mut list = Vec::new();
+        let mut map = Vec::new();
+        loop {
+            match (self.peek().cloned(), self.peek_at(1).cloned()) {
+                (None, _) => break,
+                (Some(Token::Sym(s)), _) if s == close => {
+                    self.pos += 1;
+                    break;
+                }
+                (Some(Token::Sym(",")), _) | (Some(Token::Sym(";")), _) => self.pos += 1,
+                (Some(Token::Ident(key)), Some(Token::Sym("="))) => {
+                    self.pos += 2;
+                    map.push((key, self.value()));
+                }
+                (Some(Token::Str(key)), Some(Token::Sym(":"))) => {
+                    self.pos += 2;
+                    map.push((key, self.value()));
+                }
+                (Some(Token::Sym("[")), Some(Token::Str(key)))
+                    if self.peek_at(2) == Some(&Token::Sym("]"))
+                        && self.peek_at(3) == Some(&Token::Sym("=")) =>
+                {
+                    self.pos += 4;
+                    map.push((key, self.value()));
+                }
+                _ => {
+                    let start = self.pos;
+                    list.push(self.value());
+                    if self.pos == start {
+                        // A stray closing bracket, don't loop forever.
+                        self.pos += 1;
+                    }
+                }
+            }
+        }
+        Value::Table(list, map)
+    }
+
+    /// Skip the rest of an expression up to the next separator.
+    fn skip_expression(&mut self) {
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Sym("{") | Token::Sym("[") | Token::Sym("(") => depth += 1,
+                Token::Sym("}") | Token::Sym("]") | Token::Sym(")") if depth == 0 => return,
+                Token::Sym("}") | Token::Sym("]") | Token::Sym(")") => depth -= 1,
+                Token::Sym(",") | Token::Sym(";") if depth == 0 => return,
+                Token::Ident(i) if i == "function" => {
+                    self.pos += 1;
+                    self.skip_block();
+                    continue;
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Skip a Lua block after its opening keyword up to the matching `end`.
+    fn skip_block(&mut self) {
+        let mut depth = 1;
+        while let Some(token) = self.next() {
+            if let Token::Ident(i) = token {
+                match i.as_str() {
+                    "function" | "if" | "do" | "repeat" => depth += 1,
+                    "end" | "until" => depth -= 1,
+                    _ => {}
+                }
+                if depth == 0 {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn sym_of(sym: &str) -> &'static str {
+    SYMBOLS.iter().find(|s| **s == sym).copied().unwrap_or("?")
+}
+
+/// Join Vimscript continuation lines.
+fn logical_lines(src: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in src.lines() {
+        let trimmed = line.trim_start();
+        match (trimmed.strip_prefix('\\'), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(trimmed.to_string()),
+        }
+    }
+    lines
+}
+
+/// Cut a trailing `" comment` off the arguments of a `Plug` or `dein#add`
+/// line. vim-plug only takes single quoted strings, there a `"` after
+/// whitespace starts the comment. dein#add is a function call, its comment
+/// follows the closing parenthesis.
+fn strip_comment(manager: Manager, args: &str) -> &str {
+    let mut quote = None;
+    // The opening parenthesis of dein#add is already cut off.
+    let mut depth = 1;
+    let mut prev = ' ';
+    for (i, c) in args.char_indices() {
+        match (quote, c) {
+            (Some('"'), '"') if prev != '\\' => quote = None,
+            (Some('\''), '\'') => quote = None,
+            (Some(_), _) => {}
+            (None, '"') if manager == Manager::Plug && prev.is_whitespace() => {
+                return args[..i].trim_end();
+            }
+            (None, '"') if manager == Manager::Dein && depth == 0 => return args[..i].trim_end(),
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, _) => {}
+        }
+        prev = c;
+    }
+    args
+}
+
+/// Read `Plug` and `dein#add` calls from a vimrc. Lines which can't be read
+/// are returned as notes.
+pub fn read_vim(src: &str) -> (Vec<Imported>, Vec<String>) {
+    let mut imported = Vec::new();
+    let mut notes = Vec::new();
+    for line in logical_lines(src) {
+        let (manager, args) = if let Some(rest) = line.strip_prefix("Plug ") {
+            (Manager::Plug, rest)
+        } else if let Some(rest) = line.strip_prefix("call dein#add(") {
+            (Manager::Dein, rest)
+        } else {
+            continue;
+        };
+        let mut parser = Parser::new(tokenize(strip_comment(manager, args), false));
+        let name = match parser.value() {
+            Value::Str(name) => name,
+            _ => {
+                notes.push(format!("`{line}`: can't be read"));
+                continue;
+            }
+        };
+        let options = if parser.eat(",") {
+            parser.value()
+        } else {
+            Value::Table(vec![], vec![])
+        };
+        imported.extend(translate(manager, &name, &options));
+    }
+    (imported, notes)
+}
+
+/// Read packer `use` specs or, if there are none, lazy.nvim spec tables.
+pub fn read_lua(src: &str) -> Vec<Imported> {
+    let tokens = tokenize(src, true);
+    let packer = read_packer(tokens.clone());
+    if !packer.is_empty() {
+        return packer;
+    }
+    let vim_pack = read_vim_pack(tokens.clone());
+    if !vim_pack.is_empty() {
+        return vim_pack;
+    }
+    let mut imported = Vec::new();
+    let mut parser = Parser::new(tokens);
+    while let Some(token) = parser.next() {
+        if token == Token::Sym("{") {
+            let table = parser.table("}");
+            walk_lazy(&table, &mut imported);
+        }
+    }
+    imported
+}
+
+fn read_packer(tokens: Vec<Token>) -> Vec<Imported> {
+    let mut imported = Vec::new();
+    let mut parser = Parser::new(tokens);
+    while let Some(token) = parser.next() {
+        if token != Token::Ident("use".to_string()) {
+            continue;
+        }
+        let spec = match parser.peek() {
+            Some(Token::Str(_)) | Some(Token::Sym("{")) => parser.literal(),
+            Some(Token::Sym("(")) => {
+                parser.pos += 1;
+                parser.value()
+            }
+            _ => continue,
+        };
+        imported.extend(translate_spec(Manager::Packer, &spec));
+    }
+    imported
+}
+
+/// Read the specs passed to `vim.pack.add()`.
+fn read_vim_pack(tokens: Vec<Token>) -> Vec<Imported> {
+    let call = ["vim", ".", "pack", ".", "add", "("];
+    let mut imported = Vec::new();
+    let mut parser = Parser::new(tokens);
+    while parser.peek().is_some() {
+        if !parser.looking_at(&call) {
+            parser.pos += 1;
+            continue;
+        }
+        parser.pos += call.len();
+        if let Value::Table(list, _) = parser.value() {
+            for spec in list {
+                imported.extend(translate_spec(Manager::VimPack, &spec));
+            }
+        }
+    }
+    imported
+}
+
+/// Find lazy.nvim specs in `value`. Like lazy.nvim, a table with several
+/// positional elements or without keys is a list of specs.
+fn walk_lazy(value: &Value, imported: &mut Vec<Imported>) {
+    match value {
+        Value::Str(s) if is_plugin_name(s) => imported.extend(translate_spec(Manager::Lazy, value)),
+        Value::Table(list, map) if list.len() > 1 || map.is_empty() => {
+            for v in list {
+                walk_lazy(v, imported);
+            }
+        }
+        Value::Table(list, _) => {
+            let named = list
+                .first()
+                .and_then(|v| v.as_str())
+                .is_some_and(is_plugin_name);
+            if named || value.get("dir").is_some() || value.get("url").is_some() {
+                imported.extend(translate_spec(Manager::Lazy, value));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_plugin_name(s: &str) -> bool {
+    git::is_url(s) || (s.contains('/') && !s.contains(' ') && !s.starts_with('/'))
+}
+
+/// Translate a spec which is either a plain name or a table starting with one.
+fn translate_spec(manager: Manager, spec: &Value) -> Vec<Imported> {
+    match spec {
+        Value::Str(name) => translate(manager, name, &Value::Table(vec![], vec![])),
+        Value::Table(list, _) => {
+            let name = list
+                .first()
+                .and_then(|v| v.as_str())
+                .or_else(|| spec.get("url").and_then(|v| v.as_str()))
+                .or_else(|| spec.get("src").and_then(|v| v.as_str()))
+                .or_else(|| spec.get("dir").and_then(|v| v.as_str()));
+            match name {
+                Some(name) => translate(manager, name, spec),
+                None => vec![],
+            }
+        }
+        _ => vec![],
+    }
+}
+
+fn new_package(name: &str) -> Package {
+    if name.starts_with('~') || name.starts_with('/') || name.starts_with('.') {
+        let path = settings::expand_home(name);
+        let mut p = Package::new(&path.to_string_lossy(), "default", false);
+        p.local = true;
+        p
+    } else if git::is_url(name) {
+        let short = git::name_from_url(name);
+        let mut p = Package::new(&short, "default", false);
+        if name.trim_end_matches(".git") != git::github_url(&short) {
+            p.set_url(name);
+        }
+        p
+    } else {
+        Package::new(name, "default", false)
+    }
+}
+
+/// Map the options of a spec onto a package, returns the package followed
+/// by its dependencies.
+fn translate(manager: Manager, name: &str, options: &Value) -> Vec<Imported> {
+    let mut p = new_package(name);
+    let mut skipped = Vec::new();
+    let mut deps = Vec::new();
+    let mut skip = |key: &str, value: &Value, why: &str| {
+        let why = if why.is_empty() {
+            String::new()
+        } else {
+            format!(" ({why})")
+        };
+        skipped.push(format!("{key}: {}{why}", value.describe()));
+    };
+    let options = match options {
+        Value::Table(_, map) => map.as_slice(),
+        _ => &[],
+    };
+
+    for (key, value) in options {
+        let action = match (manager, key.as_str()) {
+            (Manager::Plug, "on") | (Manager::Dein, "on_cmd") => "cmd",
+            (Manager::Packer, "cmd") | (Manager::Lazy, "cmd") => "cmd",
+            (Manager::Plug, "for") | (Manager::Dein, "on_ft") => "ft",
+            (Manager::Packer, "ft") | (Manager::Lazy, "ft") => "ft",
+            (Manager::Plug, "do") | (Manager::Packer, "run") => "build",
+            (Manager::Dein, "build") | (Manager::Lazy, "build") => "build",
+            (Manager::Dein, "lazy") | (Manager::Lazy, "lazy") | (Manager::Packer, "opt") => "opt",
+            (Manager::Packer, "requires") | (Manager::Lazy, "dependencies") => "deps",
+            (Manager::Packer, "disable") => "disable",
+            (Manager::Lazy, "enabled") => "enabled",
+            (Manager::Lazy, "version") => "version",
+            (Manager::Lazy, "submodules") => "submodules",
+            (Manager::Lazy, "url") | (Manager::Lazy, "dir") => "source",
+            (Manager::Dein, "rev") => "rev",
+            (Manager::VimPack, "version") => "pin",
+            (Manager::VimPack, "src") => "source",
+            (_, "branch") | (_, "tag") | (_, "commit") | (_, "rtp") => key.as_str(),
+            _ => "",
+        };
+        match (action, value) {
+            ("cmd", v) => match v.strings() {
+                Some(cmds) if cmds.iter().all(|c| !c.starts_with('<')) => {
+                    // An empty list loads the package manually.
+                    if let Some(first) = cmds.first() {
+                        p.set_load_command(first);
+                    }
+                    p.set_opt(true);
+                    if cmds.len() > 1 {
+                        skip(key, v, "only the first command loads the package");
+                    }
+                }
+                _ => skip(key, v, "only commands can load a package"),
+            },
+            ("ft", v) => match v.strings() {
+                Some(types) => {
+                    p.set_types(types);
+                    p.set_opt(true);
+                }
+                None => skip(key, v, ""),
+            },
+            ("build", Value::Str(cmd)) if !cmd.starts_with(':') => p.set_build_command(cmd),
+            ("build", v) => skip(key, v, "only shell commands are supported"),
+            ("opt", v) if v.truthy().is_some() => p.set_opt(v.truthy().unwrap_or(false)),
+            ("deps", v) => deps.push(v.clone()),
+            ("disable", Value::Bool(true)) | ("enabled", Value::Bool(false)) => {
+                return vec![];
+            }
+            ("disable", _) | ("enabled", _) | ("source", _) => {}
+            ("version", Value::Str(v)) => p.set_version(v),
+            ("version", Value::Bool(false)) => {}
+            ("submodules", Value::Bool(enabled)) => p.submodules.enabled = *enabled,
+            ("rev", Value::Str(rev)) if is_commit(rev) => p.set_commit(rev),
+            ("rev", v) => skip(key, v, "set `branch` or `tag` instead"),
+            ("pin", Value::Str(v)) => set_pin(&mut p, v),
+            ("pin", Value::Range(v)) => p.set_version(v),
+            ("branch", Value::Str(v)) => p.set_branch(v),
+            ("tag", Value::Str(v)) => p.set_tag(v),
+            ("commit", Value::Str(v)) => p.set_commit(v),
+            ("rtp", Value::Str(v)) => match package::check_rtp(v) {
+                Ok(rtp) => p.rtp = Some(rtp),
+                Err(_) => skip(key, value, "has to be a path inside the repository"),
+            },
+            (_, v) => skip(key, v, ""),
+        }
+    }
+
+    let mut imported = vec![Imported {
+        package: p,
+        skipped,
+    }];
+    for dep in deps {
+        match (manager, dep) {
+            (Manager::Lazy, dep) => walk_lazy(&dep, &mut imported),
+            (_, Value::Table(list, _)) => {
+                for spec in list {
+                    imported.extend(translate_spec(manager, &spec));
+                }
+            }
+            (_, spec) => imported.extend(translate_spec(manager, &spec)),
+        }
+    }
+    imported
+}
+
+fn is_commit(rev: &str) -> bool {
+    (7..=40).contains(&rev.len()) && rev.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// vim.pack takes a branch, tag or commit as `version`. Anything but a commit
+/// is kept as a branch, a clone checks it out as a tag if the remote only has
+/// a tag of that name.
+fn set_pin(p: &mut Package, version: &str) {
+    if is_commit(version) {
+        p.set_commit(version);
+    } else {
+        p.set_branch(version);
+    }
+}
+
+/// Read a lazy.nvim `lazy-lock.json` or a vim.pack `nvim-pack-lock.json`.
+///
+/// Only the vim.pack lockfile names the source of its plugins, packages are
+/// returned for those.
+pub fn read_json_lock(src: &str) -> Result<(Vec<Imported>, Vec<LockEntry>)> {
+    let docs = YamlLoader::load_from_str(src)?;
+    match docs.first() {
+        Some(doc) if doc["plugins"].as_hash().is_some() => read_vim_pack_lock(doc),
+        Some(Yaml::Hash(h)) => Ok((vec![], read_lazy_lock(h)?)),
+        _ => Err(Error::Format),
+    }
+}
+
+/// The plugins of `nvim-pack-lock.json` are keyed by directory name and record
+/// the source, the revision and the `version` of the spec. A string version is
+/// quoted, a version range is not.
+fn read_vim_pack_lock(doc: &Yaml) -> Result<(Vec<Imported>, Vec<LockEntry>)> {
+    let mut imported = Vec::new();
+    let mut entries = Vec::new();
+    for (_, plugin) in doc["plugins"].as_hash().ok_or(Error::Format)? {
+        let src = plugin["src"].as_str().ok_or(Error::Format)?;
+        let mut p = new_package(src);
+        match plugin["version"].as_str() {
+            Some(v) if v.len() > 1 && v.starts_with('\'') && v.ends_with('\'') => {
+                set_pin(&mut p, &v[1..v.len() - 1])
+            }
+            Some(v) => p.set_version(v),
+            None => {}
+        }
+        // The version may name a tag, the commit is checked out without it.
+        if let Some(rev) = plugin["rev"].as_str() {
+            entries.push(LockEntry {
+                name: p.name.clone(),
+                commit: rev.to_string(),
+                branch: None,
+            });
+        }
+        imported.push(Imported {
+            package: p,
+            skipped: vec![],
+        });
+    }
+    Ok((imported, entries))
+}
+
+/// Read the commits pinned in a lazy.nvim `lazy-lock.json`. The entries are
+/// keyed by the plugin name lazy.nvim uses, the repository name.
+fn read_lazy_lock(hash: &yaml_rust::yaml::Hash) -> Result<Vec<LockEntry>> {
+    let mut entries = Vec::new();
+    for (name, entry) in hash {
+        let name = name.as_str().ok_or(Error::Format)?;
+        let commit = entry["commit"].as_str().ok_or(Error::Format)?;
+        entries.push(LockEntry {
+            name: name.to_string(),
+            commit: commit.to_string(),
+            branch: entry["branch"].as_str().map(|s| s.to_string()),
+        });
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_vim_plug() {
+        let src = "
+\" comment
+Plug 'junegunn/fzf', { 'do': './install --all' }
+Plug 'scrooloose/nerdtree', { 'on': ['NERDTreeToggle', 'NERDTree'] }
+Plug 'rust-lang/rust.vim', {
+      \\ 'for': 'rust',
+      \\ 'branch': 'main' }
+Plug 'tpope/vim-fireplace', { 'on': '<Plug>Fire', 'frozen': 1 }
+call dein#add('Shougo/deoplete.nvim', {'lazy': 1, 'rev': '0123abc'}) \" pinned
+Plug 'SirVer/ultisnips', { 'on': [] }
+Plug 'tpope/vim-sensible' \" defaults
+Plug g:local_plugin
+";
+        let (imported, notes) = read_vim(src);
+        let names: Vec<&str> = imported.iter().map(|i| i.package.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "junegunn/fzf",
+                "scrooloose/nerdtree",
+                "rust-lang/rust.vim",
+                "tpope/vim-fireplace",
+                "Shougo/deoplete.nvim",
+                "SirVer/ultisnips",
+                "tpope/vim-sensible"
+            ]
+        );
+        assert_eq!(
+            imported[0].package.build_command.as_deref(),
+            Some("./install --all")
+        );
+        assert_eq!(
+            imported[1].package.load_command.as_deref(),
+            Some("NERDTreeToggle")
+        );
+        assert!(imported[1].package.opt);
+        assert_eq!(imported[1].skipped.len(), 1);
+        assert_eq!(imported[2].package.for_types, vec!["rust"]);
+        assert_eq!(imported[2].package.branch.as_deref(), Some("main"));
+        assert_eq!(imported[3].skipped.len(), 2);
+        assert!(imported[4].package.opt);
+        assert_eq!(imported[4].package.commit.as_deref(), Some("0123abc"));
+        assert!(imported[5].package.opt);
+        assert_eq!(imported[5].package.load_command, None);
+        assert!(imported[5].skipped.is_empty());
+        assert!(imported[6].skipped.is_empty());
+        assert_eq!(notes, ["`Plug g:local_plugin`: can't be read"]);
+    }
+
+    #[test]
+    fn import_packer() {
+        let src = r#"
+return require('packer').startup(function(use)
+  use 'wbthomason/packer.nvim'
+  -- use 'commented/out'
+  use {
+    'nvim-treesitter/nvim-treesitter',
+    run = ':TSUpdate',
+    config = function() require('x').setup({ a = 1 }) end,
+  }
+  use { 'lewis6991/gitsigns.nvim', requires = { 'nvim-lua/plenary.nvim' }, tag = 'v0.6' }
+  use { 'old/plugin', disable = true }
+end)
+"#;
+        let imported = read_lua(src);
+        let names: Vec<&str> = imported.iter().map(|i| i.package.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "wbthomason/packer.nvim",
+                "nvim-treesitter/nvim-treesitter",
+                "lewis6991/gitsigns.nvim",
+                "nvim-lua/plenary.nvim"
+            ]
+        );
+        assert_eq!(imported[1].skipped.len(), 2);
+        assert_eq!(imported[2].package.tag.as_deref(), Some("v0.6"));
+    }
+
+    #[test]
+    fn import_lazy() {
+        let src = r#"
+require("lazy").setup({
+  "folke/which-key.nvim",
+  { "folke/neoconf.nvim", cmd = "Neoconf" },
+  {
+    "nvim-telescope/telescope.nvim",
+    version = "0.1.*",
+    dependencies = { "nvim-lua/plenary.nvim", { "nvim-telescope/telescope-fzf-native.nvim", build = "make" } },
+    event = "VeryLazy",
+    opts = { defaults = { layout = "vertical" } },
+  },
+  { "https://git.example.com/me/tool.git", ft = { "lua", "vim" }, enabled = true },
+  { "disabled/plugin", enabled = false },
+}, { checker = { enabled = true } })
+"#;
+        let imported = read_lua(src);
+        let names: Vec<&str> = imported.iter().map(|i| i.package.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "folke/which-key.nvim",
+                "folke/neoconf.nvim",
+                "nvim-telescope/telescope.nvim",
+                "nvim-lua/plenary.nvim",
+                "nvim-telescope/telescope-fzf-native.nvim",
+                "me/tool"
+            ]
+        );
+        assert_eq!(imported[1].package.load_command.as_deref(), Some("Neoconf"));
+        assert_eq!(imported[2].package.version.as_deref(), Some("0.1.*"));
+        assert_eq!(imported[2].skipped.len(), 2);
+        assert_eq!(imported[4].package.build_command.as_deref(), Some("make"));
+        assert_eq!(
+            imported[5].package.url.as_deref(),
+            Some("https://git.example.com/me/tool.git")
+        );
+        assert_eq!(imported[5].package.for_types, vec!["lua", "vim"]);
+    }
+
+    #[test]
+    fn import_lazy_lock() {
+        let src = r#"{
+  "lazy.nvim": { "branch": "main", "commit": "0123456789abcdef" },
+  "plenary.nvim": { "branch": "master", "commit": "fedcba9876543210" }
+}"#;
+        let (imported, entries) = read_json_lock(src).unwrap();
+        assert!(imported.is_empty());
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].name, "plenary.nvim");
+        assert_eq!(entries[1].branch.as_deref(), Some("master"));
+    }
+
+    #[test]
+    fn import_vim_pack() {
+        let src = r#"
+vim.pack.add({
+  'https://github.com/nvim-lua/plenary.nvim',
+  { src = 'https://github.com/saghen/blink.cmp', version = vim.version.range('1.*') },
+  { src = 'https://gitlab.com/me/tool', version = 'main', data = { x = 1 } },
+})
+"#;
+        let imported = read_lua(src);
+        let names: Vec<&str> = imported.iter().map(|i| i.package.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["nvim-lua/plenary.nvim", "saghen/blink.cmp", "me/tool"]
+        );
+        assert_eq!(imported[0].package.url, None);
+        assert_eq!(imported[1].package.version.as_deref(), Some("1.*"));
+        assert_eq!(
+            imported[2].package.url.as_deref(),
+            Some("https://gitlab.com/me/tool")
+        );
+        assert_eq!(imported[2].package.branch.as_deref(), Some("main"));
+        assert_eq!(imported[2].skipped.len(), 1);
+    }
+
+    #[test]
+    fn import_vim_pack_lock() {
+        let src = r#"{
+  "plugins": {
+    "blink.cmp": { "rev": "0123456789abcdef", "src": "https://github.com/saghen/blink.cmp", "version": "1.*" },
+    "nvim-lspconfig": { "rev": "fedcba9876543210", "src": "https://github.com/neovim/nvim-lspconfig", "version": "'v2.1.0'" }
+  }
+}"#;
+        let (imported, entries) = read_json_lock(src).unwrap();
+        assert_eq!(imported[0].package.version.as_deref(), Some("1.*"));
+        assert_eq!(imported[1].package.name, "neovim/nvim-lspconfig");
+        assert_eq!(imported[1].package.branch.as_deref(), Some("v2.1.0"));
+        assert_eq!(entries[1].branch, None);
+        assert_eq!(entries[1].name, "neovim/nvim-lspconfig");
+        assert_eq!(entries[1].commit, "fedcba9876543210");
+    }
+}
@@ -2,11 +2,16 @@ pub mod package;
 #[macro_use]
 pub mod utils;
 
+pub mod archive;
 pub mod cli;
 pub mod cmd;
 pub mod echo;
 pub mod error;
+pub mod export;
 pub mod git;
+pub mod import;
+pub mod lock;
+pub mod settings;
 pub mod task;
 
 pub use error::{Error, Result};
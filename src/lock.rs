@@ -0,0 +1,213 @@
+use crate::git;
+use crate::package::{Package, PACK_CONFIG_DIR};
+use crate::{Error, Result};
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+const LOCKFILE_HEADER: &[u8] = b"# vim: ft=yaml
+#
+# Generated by pack. DO NOT EDIT!
+
+";
+
+lazy_static! {
+    static ref LOCK_FILE: PathBuf = (*PACK_CONFIG_DIR).join("packfile.lock");
+    static ref HISTORY_FILE: PathBuf = (*PACK_CONFIG_DIR).join("history");
+}
+
+/// The resolved state of a single package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockEntry {
+    pub name: String,
+    /// Commit the package was checked out at
+    pub commit: String,
+    /// Branch the commit was found on
+    pub branch: Option<String>,
+}
+
+impl LockEntry {
+    pub fn from_yaml(doc: &Yaml) -> Result<LockEntry> {
+        let name = doc["name"]
+            .as_str()
+            .map(|s| s.to_string())
+            .ok_or(Error::Format)?;
+        let commit = doc["commit"]
+            .as_str()
+            .map(|s| s.to_string())
+            .ok_or(Error::Format)?;
+        let branch = doc["branch"].as_str().map(|s| s.to_string());
+        Ok(LockEntry {
+            name,
+            commit,
+            branch,
+        })
+    }
+
+    pub fn into_yaml(self) -> Yaml {
+        let mut doc = Hash::new();
+        doc.insert(Yaml::from_str("name"), Yaml::from_str(&self.name));
+        doc.insert(Yaml::from_str("commit"), Yaml::from_str(&self.commit));
+        if let Some(ref b) = self.branch {
+            doc.insert(Yaml::from_str("branch"), Yaml::from_str(b));
+        }
+        Yaml::Hash(doc)
+    }
+
+    /// Read the current state of an installed package.
+    ///
+    /// Returns `None` for local and archive packages and packages which are not
+    /// installed.
+    pub fn resolve(pack: &Package) -> Option<LockEntry> {
+        if pack.local || pack.archive.is_some() || !pack.is_installed() {
+            return None;
+        }
+        match git::head(pack.repo_path()) {
+            Ok((commit, branch)) => Some(LockEntry {
+                name: pack.name.clone(),
+                commit,
+                branch,
+            }),
+            Err(e) => {
+                log::info!("fail to resolve {}: {e}", &pack.name);
+                None
+            }
+        }
+    }
+}
+
+/**
+ * Load the lockfile if one is available.
+ */
+pub fn fetch() -> Result<Vec<LockEntry>> {
+    if LOCK_FILE.is_file() {
+        fetch_from_lockfile(&*LOCK_FILE)
+            .map_err(|e| Error::PackFile(format!("Fail to parse lockfile: {e}")))
+    } else {
+        Ok(vec![])
+    }
+}
+
+/**
+ * Load the state of the packages before the last update.
+ */
+pub fn fetch_history() -> Result<Vec<LockEntry>> {
+    if HISTORY_FILE.is_file() {
+        fetch_from_lockfile(&*HISTORY_FILE)
+            .map_err(|e| Error::PackFile(format!("Fail to parse history: {e}")))
+    } else {
+        Ok(vec![])
+    }
+}
+
+pub fn fetch_from_lockfile<P: AsRef<Path>>(lockfile: P) -> Result<Vec<LockEntry>> {
+    let mut data = String::new();
+    File::open(lockfile.as_ref())?.read_to_string(&mut data)?;
+    let docs = YamlLoader::load_from_str(&data)?;
+
+    let mut ret = Vec::new();
+    if let Some(doc) = docs.first().and_then(|d| d.as_vec()) {
+        for d in doc {
+            ret.push(LockEntry::from_yaml(d)?);
+        }
+    }
+    Ok(ret)
+}
+
+pub fn save(entries: Vec<LockEntry>) -> Result<()> {
+    save_to_lockfile(&*LOCK_FILE, entries)
+}
+
+/// Record the current commit of `packs` so an update can be rolled back.
+///
+/// Packages which are not part of this update keep their previous entry.
+pub fn save_history(packs: &[Package]) -> Result<()> {
+    let new = packs.iter().filter_map(LockEntry::resolve);
+    save_to_lockfile(&*HISTORY_FILE, merge(fetch_history()?, new))
+}
+
+/// Replace the entries in `old` by the ones in `new` with the same name.
+fn merge<I: IntoIterator<Item = LockEntry>>(mut old: Vec<LockEntry>, new: I) -> Vec<LockEntry> {
+    for entry in new {
+        old.retain(|e| e.name != entry.name);
+        old.push(entry);
+    }
+    old.sort_by(|a, b| a.name.cmp(&b.name));
+    old
+}
+
+pub fn save_to_lockfile<P: AsRef<Path>>(lockfile: P, entries: Vec<LockEntry>) -> Result<()> {
+    let entries = entries
+        .into_iter()
+        .map(|e| e.into_yaml())
+        .collect::<Vec<Yaml>>();
+    let doc = Yaml::Array(entries);
+    let mut out = String::new();
+    {
+        let mut emitter = YamlEmitter::new(&mut out);
+        emitter.dump(&doc)?;
+    }
+    if !PACK_CONFIG_DIR.is_dir() {
+        fs::create_dir_all(&*PACK_CONFIG_DIR)?;
+    }
+    let mut f = File::create(lockfile.as_ref())?;
+    f.write_all(LOCKFILE_HEADER)?;
+    f.write_all(out.as_bytes())?;
+    Ok(())
+}
+
+/// Record the current commit of every package.
+///
+/// Packages whose state can't be read keep their previous entry.
+pub fn update(packs: &[Package]) -> Result<()> {
+    let old = fetch()?;
+    let mut entries = packs
+        .iter()
+        .filter_map(|p| LockEntry::resolve(p).or_else(|| find(&old, &p.name).cloned()))
+        .collect::<Vec<LockEntry>>();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    save(entries)
+}
+
+pub fn find<'a>(entries: &'a [LockEntry], name: &str) -> Option<&'a LockEntry> {
+    entries.iter().find(|e| e.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_entry_yaml_roundtrip() {
+        let entry = LockEntry {
+            name: "user/repo".to_string(),
+            commit: "0123456789abcdef".to_string(),
+            branch: Some("main".to_string()),
+        };
+        let back = LockEntry::from_yaml(&entry.clone().into_yaml()).unwrap();
+        assert_eq!(entry, back);
+    }
+
+    #[test]
+    fn lock_entry_requires_commit() {
+        let docs = YamlLoader::load_from_str("name: user/repo").unwrap();
+        assert!(LockEntry::from_yaml(&docs[0]).is_err());
+    }
+
+    #[test]
+    fn merge_history() {
+        let entry = |name: &str, commit: &str| LockEntry {
+            name: name.to_string(),
+            commit: commit.to_string(),
+            branch: None,
+        };
+        let old = vec![entry("a", "1"), entry("b", "1")];
+        let merged = merge(old, vec![entry("b", "2"), entry("c", "2")]);
+        assert_eq!(merged, vec![entry("a", "1"), entry("b", "2"), entry("c", "2")]);
+    }
+}
@@ -14,12 +14,18 @@ fn main() -> Result<()>{
     let cmd = app_m.cmd;
     match cmd {
         cli::Command::List(args) => cmd::list::list_packages(args),
-        cli::Command::Install(args)=> cmd::install::install_plugins(args),
+        cli::Command::Install(args)=> cmd::install::install_plugins(*args),
         cli::Command::Uninstall(args)=> cmd::uninstall::exec(args),
         cli::Command::Config(args) => cmd::config::config(args),
         cli::Command::Move(args) => cmd::move_cmd::move_plugin(args),
         cli::Command::Update(args) => cmd::update::exec(args),
+        cli::Command::Outdated(args) => cmd::outdated::exec(args),
+        cli::Command::Rollback(args) => cmd::rollback::exec(args),
+        cli::Command::Snapshot(args) => cmd::snapshot::exec(args),
         cli::Command::Generate => cmd::generate::update_packfile(),
+        cli::Command::Gc => cmd::gc::exec(),
+        cli::Command::Import(args) => cmd::import::exec(args),
+        cli::Command::Export(args) => cmd::export::exec(args),
         cli::Command::Completions(_args) => {
             // TODO
             //let shell = m.value_of("SHELL").unwrap();
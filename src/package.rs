@@ -1,17 +1,36 @@
+use crate::archive;
+use crate::git;
+use crate::settings::{self, Settings};
 use crate::{Error, Result};
 
 use std::env;
 use std::fmt;
 use std::fs::{self, File};
 use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
+use std::os::unix::fs::symlink;
+use std::path::{Component, Path, PathBuf};
 use std::process;
+use std::time::Duration;
 
 use lazy_static::lazy_static;
 use yaml_rust::yaml::Hash;
 use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
 
 const PACK_PLUGIN_FILE: &str = "_pack.vim";
+const PACK_LUA_PLUGIN_FILE: &str = "_pack.lua";
+const LUA_PLUGIN_HEADER: &str = "-- Generated by pack. DO NOT EDIT!
+
+local group = vim.api.nvim_create_augroup('npack', { clear = true })
+
+local function pack_command(name, repo)
+    vim.api.nvim_create_user_command(name, function(opts)
+        pcall(vim.api.nvim_del_user_command, name)
+        vim.cmd('packadd ' .. repo)
+        local range = opts.line1 == opts.line2 and '' or (opts.line1 .. ',' .. opts.line2)
+        vim.cmd(range .. name .. (opts.bang and '!' or '') .. ' ' .. opts.args)
+    end, { nargs = '*', range = true, bang = true })
+end
+";
 const PLUGIN_HEADER: &str = "\" Generated by pack. DO NOT EDIT!
 
 scriptencoding utf-8
@@ -21,6 +40,9 @@ function! s:do_cmd(cmd, bang, start, end, args)
 endfunction
 ";
 
+/// Version of the packfile format written by this release.
+const PACKFILE_VERSION: i64 = 1;
+
 const PACKFILE_HEADER: &[u8] = b"# vim: ft=yaml
 #
 # Generated by pack.
@@ -34,9 +56,10 @@ lazy_static! {
             let home = dirs::home_dir().expect("No home directory found");
             home.join(".config").join("nvim")
         });
-    static ref PACK_DIR: PathBuf = (*BASE_DIR).join("pack");
-    static ref PACK_CONFIG_DIR: PathBuf = (*BASE_DIR).join(".pack");
-    static ref PACK_FILE: PathBuf = (*PACK_CONFIG_DIR).join("packfile");
+    pub(crate) static ref PACK_DIR: PathBuf = (*BASE_DIR).join("pack");
+    pub(crate) static ref PACK_CONFIG_DIR: PathBuf = (*BASE_DIR).join(".pack");
+    pub(crate) static ref PACK_FILE: PathBuf = (*PACK_CONFIG_DIR).join("packfile");
+    pub(crate) static ref PACK_REPOS_DIR: PathBuf = (*PACK_CONFIG_DIR).join("repos");
     static ref PACK_PLUGIN_DIR: PathBuf = (*BASE_DIR).join("plugin");
 }
 
@@ -45,8 +68,22 @@ pub struct Package {
     pub name: String,
     pub category: String,
     pub opt: bool,
-    /// Load this package on this command
+    /// Remote url, defaults to the GitHub repository named by `name`
+    pub url: Option<String>,
+    /// Urls tried in order when cloning or fetching from `url` fails
+    pub fallback_urls: Vec<String>,
+    /// Branch to check out
     pub branch: Option<String>,
+    /// Tag to hold the package at
+    pub tag: Option<String>,
+    /// Commit to hold the package at
+    pub commit: Option<String>,
+    /// Semver range matched against the tags of the repository
+    pub version: Option<String>,
+    /// History depth, overrides the global setting, 0 fetches everything
+    pub depth: Option<u32>,
+    /// Submodules checked out on install and update
+    pub submodules: git::Submodules,
     /// Load this package on this command
     pub load_command: Option<String>,
     /// Load this package for these types
@@ -55,6 +92,24 @@ pub struct Package {
     pub build_command: Option<String>,
     /// Local plugin
     pub local: bool,
+    /// How a local plugin is put into `pack/`
+    pub local_mode: LocalMode,
+    /// Subdirectory of the repository holding the plugin
+    pub rtp: Option<String>,
+    /// Only check out commits and tags with a valid signature
+    pub verify: bool,
+    /// Url or path of a release archive used instead of a git repository
+    pub archive: Option<String>,
+    /// Expected sha256 checksum of the archive
+    pub sha256: Option<String>,
+}
+
+/// How a local plugin is put into `pack/`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalMode {
+    Symlink,
+    /// Copy the directory and sync it on update
+    Copy,
 }
 
 impl Package {
@@ -62,12 +117,24 @@ impl Package {
         Package {
             name: name.to_string(),
             category: category.to_string(),
+            url: None,
+            fallback_urls: Vec::new(),
             branch: None,
+            tag: None,
+            commit: None,
+            version: None,
+            depth: None,
+            submodules: git::Submodules::default(),
             opt,
             load_command: None,
             for_types: Vec::new(),
             build_command: None,
             local: false,
+            local_mode: LocalMode::Symlink,
+            rtp: None,
+            verify: false,
+            archive: None,
+            sha256: None,
         }
     }
 
@@ -87,10 +154,83 @@ impl Package {
         self.load_command = Some(cmd.to_string())
     }
 
+    pub fn set_url(&mut self, url: &str) {
+        self.url = Some(url.to_string())
+    }
+
+    /// The url used to clone and fetch this package.
+    pub fn remote_url(&self) -> String {
+        self.url.clone().unwrap_or_else(|| git::github_url(&self.name))
+    }
+
     pub fn set_branch(&mut self, branch_name: &str) {
         self.branch = Some(branch_name.to_string())
     }
 
+    pub fn set_tag(&mut self, tag: &str) {
+        self.tag = Some(tag.to_string())
+    }
+
+    pub fn set_commit(&mut self, commit: &str) {
+        self.commit = Some(commit.to_string())
+    }
+
+    pub fn set_version(&mut self, version: &str) {
+        self.version = Some(version.to_string())
+    }
+
+    /// The revision the package is held at, a commit takes precedence over a
+    /// tag and a tag over a version.
+    pub fn pin(&self) -> git::Pin {
+        if let Some(ref c) = self.commit {
+            git::Pin::Commit(c.clone())
+        } else if let Some(ref t) = self.tag {
+            git::Pin::Tag(t.clone())
+        } else if let Some(ref v) = self.version {
+            git::Pin::Version(v.clone())
+        } else {
+            git::Pin::Head
+        }
+    }
+
+    /// Collect how the archive of this package is downloaded, `None` for git
+    /// packages.
+    pub fn archive_source(&self, settings: &Settings) -> Option<archive::Source> {
+        let mut source = archive::Source::new(self.archive.as_ref()?.as_str());
+        source.sha256 = self.sha256.clone();
+        source.timeout = settings.timeout.map(|t| Duration::from_secs(u64::from(t)));
+        source.offline = settings.offline;
+        Some(source)
+    }
+
+    pub fn set_depth(&mut self, depth: u32) {
+        self.depth = Some(depth)
+    }
+
+    /// Collect how this package is cloned and fetched.
+    pub fn spec(&self, settings: &Settings) -> git::Spec {
+        let mut spec = git::Spec::new(settings.rewrite_url(&self.remote_url()));
+        spec.fallback_urls = self
+            .fallback_urls
+            .iter()
+            .map(|u| settings.rewrite_url(u))
+            .collect();
+        spec.branch = self.branch.clone();
+        spec.pin = self.pin();
+        spec.depth = self.depth.or(settings.depth).filter(|d| *d > 0);
+        spec.ssh_keys = settings.ssh_keys.clone();
+        spec.submodules = self.submodules.clone();
+        spec.retries = settings.retries;
+        spec.retry_delay = Duration::from_secs(u64::from(settings.retry_delay));
+        spec.timeout = settings.timeout.map(|t| Duration::from_secs(u64::from(t)));
+        spec.cache = settings.mirror_cache;
+        spec.offline = settings.offline;
+        if self.verify {
+            spec.verify = Some(settings.verify.clone());
+        }
+        spec
+    }
+
     pub fn set_types(&mut self, types: Vec<String>) {
         self.for_types = types
     }
@@ -109,10 +249,45 @@ impl Package {
             .as_str()
             .map(|s| s.to_string())
             .ok_or(Error::Format)?;
+        let url = doc["url"].as_str().map(|s| s.to_string());
+        let fallback_urls = settings::string_list(&doc["fallback_urls"])?;
         let branch = doc["branch"].as_str().map(|s| s.to_string());
+        let tag = doc["tag"].as_str().map(|s| s.to_string());
+        let commit = doc["commit"].as_str().map(|s| s.to_string());
+        let version = doc["version"].as_str().map(|s| s.to_string());
+        let depth = settings::optional_u32(&doc["depth"])?;
+        let submodules = match &doc["submodules"] {
+            Yaml::BadValue | Yaml::Null => git::Submodules::default(),
+            Yaml::Boolean(enabled) => git::Submodules {
+                enabled: *enabled,
+                ..git::Submodules::default()
+            },
+            d @ Yaml::Hash(_) => git::Submodules {
+                enabled: true,
+                include: settings::string_list(&d["include"])?,
+                exclude: settings::string_list(&d["exclude"])?,
+            },
+            _ => return Err(Error::Format),
+        };
         let cmd = doc["on"].as_str().map(|s| s.to_string());
         let build = doc["build"].as_str().map(|s| s.to_string());
         let is_local = doc["local"].as_bool().unwrap_or(false);
+        let rtp = match doc["rtp"].as_str().or_else(|| doc["subdir"].as_str()) {
+            Some(rtp) => Some(check_rtp(rtp)?),
+            None => None,
+        };
+        let local_mode = match doc["local_mode"].as_str() {
+            None | Some("symlink") => LocalMode::Symlink,
+            Some("copy") => LocalMode::Copy,
+            Some(_) => return Err(Error::Format),
+        };
+        let archive = doc["archive"].as_str().map(|s| s.to_string());
+        let sha256 = doc["sha256"].as_str().map(|s| s.to_string());
+        let verify = match &doc["verify"] {
+            Yaml::BadValue | Yaml::Null => false,
+            Yaml::String(s) if s == "signature" => true,
+            _ => return Err(Error::Format),
+        };
 
         let types = match doc["for"].as_vec() {
             Some(f) => {
@@ -129,11 +304,23 @@ impl Package {
             name,
             category,
             opt,
+            url,
+            fallback_urls,
             branch,
+            tag,
+            commit,
+            version,
+            depth,
+            submodules,
             load_command: cmd,
             for_types: types,
             build_command: build,
             local: is_local,
+            local_mode,
+            rtp,
+            verify,
+            archive,
+            sha256,
         })
     }
 
@@ -143,15 +330,68 @@ impl Package {
         doc.insert(Yaml::from_str("category"), Yaml::from_str(&self.category));
         doc.insert(Yaml::from_str("opt"), Yaml::Boolean(self.opt));
         doc.insert(Yaml::from_str("local"), Yaml::Boolean(self.local));
+        if self.local_mode == LocalMode::Copy {
+            doc.insert(Yaml::from_str("local_mode"), Yaml::from_str("copy"));
+        }
+        if let Some(ref c) = self.url {
+            doc.insert(Yaml::from_str("url"), Yaml::from_str(c));
+        }
+        if !self.fallback_urls.is_empty() {
+            let urls = self
+                .fallback_urls
+                .iter()
+                .map(|e| Yaml::from_str(e))
+                .collect::<Vec<Yaml>>();
+            doc.insert(Yaml::from_str("fallback_urls"), Yaml::Array(urls));
+        }
         if let Some(ref c) = self.branch {
             doc.insert(Yaml::from_str("branch"), Yaml::from_str(c));
         }
+        if let Some(ref c) = self.tag {
+            doc.insert(Yaml::from_str("tag"), Yaml::from_str(c));
+        }
+        if let Some(ref c) = self.commit {
+            doc.insert(Yaml::from_str("commit"), Yaml::from_str(c));
+        }
+        if let Some(ref c) = self.version {
+            doc.insert(Yaml::from_str("version"), Yaml::from_str(c));
+        }
+        if let Some(d) = self.depth {
+            doc.insert(Yaml::from_str("depth"), Yaml::Integer(i64::from(d)));
+        }
+        if !self.submodules.enabled {
+            doc.insert(Yaml::from_str("submodules"), Yaml::Boolean(false));
+        } else if self.submodules != git::Submodules::default() {
+            let list = |names: &[String]| {
+                Yaml::Array(names.iter().map(|e| Yaml::from_str(e)).collect::<Vec<Yaml>>())
+            };
+            let mut subs = Hash::new();
+            if !self.submodules.include.is_empty() {
+                subs.insert(Yaml::from_str("include"), list(&self.submodules.include));
+            }
+            if !self.submodules.exclude.is_empty() {
+                subs.insert(Yaml::from_str("exclude"), list(&self.submodules.exclude));
+            }
+            doc.insert(Yaml::from_str("submodules"), Yaml::Hash(subs));
+        }
         if let Some(ref c) = self.load_command {
             doc.insert(Yaml::from_str("on"), Yaml::from_str(c));
         }
         if let Some(ref c) = self.build_command {
             doc.insert(Yaml::from_str("build"), Yaml::from_str(c));
         }
+        if let Some(ref c) = self.rtp {
+            doc.insert(Yaml::from_str("rtp"), Yaml::from_str(c));
+        }
+        if self.verify {
+            doc.insert(Yaml::from_str("verify"), Yaml::from_str("signature"));
+        }
+        if let Some(ref c) = self.archive {
+            doc.insert(Yaml::from_str("archive"), Yaml::from_str(c));
+        }
+        if let Some(ref c) = self.sha256 {
+            doc.insert(Yaml::from_str("sha256"), Yaml::from_str(c));
+        }
         if !self.for_types.is_empty() {
             let types = self
                 .for_types
@@ -187,18 +427,54 @@ impl Package {
         PACK_DIR.join(&self.category).join(folder).join(repo)
     }
 
-    pub fn config_path(&self) -> PathBuf {
+    /// Where the repository is checked out. Packages with a `rtp` keep the
+    /// whole repository outside of `pack/` and only link the subdirectory.
+    pub fn repo_path(&self) -> PathBuf {
+        match self.rtp {
+            Some(_) if !self.local => PACK_REPOS_DIR.join(self.name.replace('/', "-")),
+            _ => self.path(),
+        }
+    }
+
+    /// Link the `rtp` subdirectory of the checkout into `pack/`.
+    pub fn link_rtp(&self) -> Result<()> {
+        let rtp = match self.rtp {
+            Some(ref rtp) if !self.local => rtp,
+            _ => return Ok(()),
+        };
+        let src = self.repo_path().join(rtp);
+        if !src.is_dir() {
+            return Err(Error::NoSubdir(rtp.clone()));
+        }
+        let path = self.path();
+        if path.symlink_metadata().is_ok() {
+            fs::remove_file(&path)?;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        symlink(src, path)?;
+        Ok(())
+    }
+
+    fn config_name(&self) -> String {
         let name = if self.local {
             self.basename().to_string()
         } else {
             self.name.replace('/', "-")
         };
-        let name = if name.ends_with(".vim") {
-            name
-        } else {
-            format!("{}.vim", &name)
-        };
-        PACK_CONFIG_DIR.join(name)
+        match name.strip_suffix(".vim") {
+            Some(n) => n.to_string(),
+            None => name,
+        }
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        PACK_CONFIG_DIR.join(format!("{}.vim", self.config_name()))
+    }
+
+    pub fn lua_config_path(&self) -> PathBuf {
+        PACK_CONFIG_DIR.join(format!("{}.lua", self.config_name()))
     }
 
     pub fn repo(&self) -> (&str, &str) {
@@ -213,7 +489,7 @@ impl Package {
 
     pub fn try_build(&self) -> Result<()> {
         if let Some(ref c) = self.build_command {
-            let path = self.path();
+            let path = self.repo_path();
             let p = process::Command::new("sh")
                 .arg("-c")
                 .arg(c)
@@ -235,6 +511,17 @@ impl Package {
 impl fmt::Display for Package {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let name = if self.opt { "opt" } else { "start" };
+        let url = match (&self.url, &self.archive) {
+            (_, Some(a)) => format!(" [Archive {a}]"),
+            (Some(u), None) => format!(" [From {u}]"),
+            (None, None) => "".to_string(),
+        };
+        let pin = match self.pin() {
+            git::Pin::Head => "".to_string(),
+            git::Pin::Tag(t) => format!(" [Tag {t}]"),
+            git::Pin::Commit(c) => format!(" [Commit {c}]"),
+            git::Pin::Version(v) => format!(" [Version {v}]"),
+        };
         let on = match self.load_command {
             Some(ref c) => format!(" [Load on `{c}`]"),
             None => "".to_string(),
@@ -246,43 +533,142 @@ impl fmt::Display for Package {
         } else {
             "".to_string()
         };
+        let rtp = match self.rtp {
+            Some(ref r) => format!(" [Rtp {r}]"),
+            None => "".to_string(),
+        };
         write!(
             f,
-            "{} => pack/{}/{}{}{}",
-            &self.name, &self.category, name, on, types
+            "{} => pack/{}/{}{}{}{}{}{}",
+            &self.name, &self.category, name, url, pin, rtp, on, types
         )
     }
 }
 
+/// Check that `rtp` stays inside the repository, a packfile must not link
+/// arbitrary directories into `pack/`.
+pub fn check_rtp(rtp: &str) -> Result<String> {
+    let inside = Path::new(rtp)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !inside {
+        return Err(Error::InvalidSubdir(rtp.to_string()));
+    }
+    Ok(rtp.trim_end_matches('/').to_string())
+}
+
 /**
  * Load the default packfile if one is available.
  */
 pub fn fetch() -> Result<Vec<Package>> {
-    if PACK_FILE.is_file() {
-        fetch_from_packfile(&*PACK_FILE)
-            .map_err(|e| Error::PackFile(format!("Fail to parse packfile: {e}")))
-    } else {
-        Ok(vec![])
+    if !PACK_FILE.is_file() {
+        return Ok(vec![]);
     }
+    let (packs, version) = fetch_from_packfile(&*PACK_FILE)
+        .map_err(|e| Error::PackFile(format!("Fail to parse packfile: {e}")))?;
+    if version < PACKFILE_VERSION {
+        let backup = PACK_FILE.with_extension(format!("v{version}.bak"));
+        fs::copy(&*PACK_FILE, &backup)?;
+        println!(
+            "Migrated packfile to version {PACKFILE_VERSION}, the old one is kept at {}",
+            backup.display()
+        );
+        save(packs.clone())?;
+    }
+    Ok(packs)
 }
 
 /**
- * Load the packfile and fetch all the stored packages
+ * Load the packfile and fetch all the stored packages, also returns the
+ * version the file was written in.
  */
-fn fetch_from_packfile<P: AsRef<Path>>(packfile: P) -> Result<Vec<Package>> {
+pub(crate) fn fetch_from_packfile<P: AsRef<Path>>(packfile: P) -> Result<(Vec<Package>, i64)> {
     let mut data = String::new();
     File::open(packfile.as_ref())?.read_to_string(&mut data)?;
     let docs = YamlLoader::load_from_str(&data)?;
+    let doc = match docs.into_iter().next() {
+        Some(doc) => doc,
+        None => return Ok((vec![], PACKFILE_VERSION)),
+    };
+    let (doc, version) = migrate(doc)?;
 
     let mut ret = Vec::new();
-    if !docs.is_empty() {
-        if let Some(doc) = docs[0].as_vec() {
-            for d in doc {
-                ret.push(Package::from_yaml(d)?);
-            }
+    // A missing list is a broken packfile, not an empty one, it would be
+    // overwritten on the next save.
+    let packages = doc["packages"].as_vec().ok_or(Error::Format)?;
+    for (i, d) in packages.iter().enumerate() {
+        let pack = Package::from_yaml(d).map_err(|e| {
+            let name = d["name"].as_str().map_or(format!("#{}", i + 1), |n| n.to_string());
+            Error::PackFile(format!("package {name}: {e}"))
+        })?;
+        ret.push(pack);
+    }
+    Ok((ret, version))
+}
+
+/// Bring a packfile document up to `PACKFILE_VERSION`, one version at a time.
+/// Returns the migrated document and the version it was written in.
+fn migrate(mut doc: Yaml) -> Result<(Yaml, i64)> {
+    let from = match doc {
+        Yaml::Array(_) | Yaml::Null => 0,
+        Yaml::Hash(_) => doc["version"].as_i64().ok_or(Error::Format)?,
+        _ => return Err(Error::Format),
+    };
+    if from > PACKFILE_VERSION {
+        return Err(Error::PackFile(format!(
+            "version {from} is newer than the supported version {PACKFILE_VERSION}, please upgrade npack"
+        )));
+    }
+    let mut version = from;
+    while version < PACKFILE_VERSION {
+        doc = match version {
+            0 => migrate_v0(doc)?,
+            _ => unreachable!("no migration from version {}", version),
+        };
+        version += 1;
+    }
+    Ok((doc, from))
+}
+
+/// Version 0 is the bare list of packages written by pack and npack before
+/// the packfile got a version. pack didn't always write `category` and `opt`
+/// and could keep `for` as a comma separated string. It also never updated
+/// `docs` submodules, which is kept as an exclude.
+fn migrate_v0(doc: Yaml) -> Result<Yaml> {
+    let key = Yaml::from_str;
+    let mut packages = Vec::new();
+    for entry in doc.into_vec().unwrap_or_default() {
+        let mut entry = match entry {
+            Yaml::Hash(h) => h,
+            _ => return Err(Error::Format),
+        };
+        if !entry.contains_key(&key("category")) {
+            entry.insert(key("category"), key("default"));
+        }
+        if !entry.contains_key(&key("opt")) {
+            entry.insert(key("opt"), Yaml::Boolean(false));
         }
+        if let Some(Yaml::String(types)) = entry.get(&key("for")).cloned() {
+            let types = types.split(',').map(|t| key(t.trim())).collect();
+            entry.insert(key("for"), Yaml::Array(types));
+        }
+        let git = !entry.contains_key(&key("archive"))
+            && entry.get(&key("local")) != Some(&Yaml::Boolean(true));
+        if git && !entry.contains_key(&key("submodules")) {
+            let mut submodules = Hash::new();
+            submodules.insert(key("exclude"), Yaml::Array(vec![key("docs")]));
+            entry.insert(key("submodules"), Yaml::Hash(submodules));
+        }
+        packages.push(Yaml::Hash(entry));
     }
-    Ok(ret)
+    Ok(packfile_doc(packages))
+}
+
+fn packfile_doc(packages: Vec<Yaml>) -> Yaml {
+    let mut doc = Hash::new();
+    doc.insert(Yaml::from_str("version"), Yaml::Integer(PACKFILE_VERSION));
+    doc.insert(Yaml::from_str("packages"), Yaml::Array(packages));
+    Yaml::Hash(doc)
 }
 
 pub fn save(packs: Vec<Package>) -> Result<()> {
@@ -290,7 +676,7 @@ pub fn save(packs: Vec<Package>) -> Result<()> {
         .into_iter()
         .map(|e| e.into_yaml())
         .collect::<Vec<Yaml>>();
-    let doc = Yaml::Array(packs);
+    let doc = packfile_doc(packs);
     let mut out = String::new();
     {
         let mut emitter = YamlEmitter::new(&mut out);
@@ -299,56 +685,124 @@ pub fn save(packs: Vec<Package>) -> Result<()> {
     if !PACK_CONFIG_DIR.is_dir() {
         fs::create_dir_all(&*PACK_CONFIG_DIR)?;
     }
-    let mut f = File::create(&*PACK_FILE)?;
+    // Write next to the packfile first so it is never left half written.
+    let tmp = PACK_FILE.with_extension("tmp");
+    let mut f = File::create(&tmp)?;
     f.write_all(PACKFILE_HEADER)?;
     f.write_all(out.as_bytes())?;
+    fs::rename(&tmp, &*PACK_FILE)?;
     Ok(())
 }
 
+/// Write the loader into `plugin/`, Vimscript or Lua depending on the
+/// `loader` setting. The loader of the other language is removed.
 pub fn update_pack_plugin(packs: &[Package]) -> Result<()> {
     if !PACK_PLUGIN_DIR.is_dir() {
         fs::create_dir_all(&*PACK_PLUGIN_DIR)?;
     }
 
-    let mut plugin_file = File::create(PACK_PLUGIN_DIR.join(PACK_PLUGIN_FILE))?;
-    plugin_file.write_all(format!("{PLUGIN_HEADER}\n\n").as_bytes())?;
+    let lua = settings::fetch()?.lua_loader;
+    let (file, stale, loader) = if lua {
+        (PACK_LUA_PLUGIN_FILE, PACK_PLUGIN_FILE, lua_loader(packs)?)
+    } else {
+        (PACK_PLUGIN_FILE, PACK_LUA_PLUGIN_FILE, vim_loader(packs)?)
+    };
+    File::create(PACK_PLUGIN_DIR.join(file))?.write_all(loader.as_bytes())?;
+    let stale = PACK_PLUGIN_DIR.join(stale);
+    if stale.is_file() {
+        fs::remove_file(stale)?;
+    }
+    Ok(())
+}
 
-    let mut buf = String::new();
-    for (pkg, path) in packs.iter().map(|x| (x, x.config_path())) {
-        buf.clear();
-        let mut written = false;
+/// Read the Vimscript and Lua config snippets of a package.
+fn read_configs(pkg: &Package) -> Result<(Option<String>, Option<String>)> {
+    let read = |path: PathBuf| -> Result<Option<String>> {
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let mut buf = String::new();
+        File::open(&path)?.read_to_string(&mut buf)?;
+        Ok(Some(buf))
+    };
+    Ok((read(pkg.config_path())?, read(pkg.lua_config_path())?))
+}
+
+fn vim_loader(packs: &[Package]) -> Result<String> {
+    let mut out = format!("{PLUGIN_HEADER}\n\n");
+    for pkg in packs {
+        let (vim, lua) = read_configs(pkg)?;
+        let has_loader = pkg.load_command.is_some() || !pkg.for_types.is_empty();
+        if !has_loader && vim.is_none() && lua.is_none() {
+            continue;
+        }
+        out.push_str(&format!("\" {}\n", &pkg.name));
+        let (_, repo) = pkg.repo();
 
         if let Some(ref cmd) = pkg.load_command {
-            plugin_file.write_all(format!("\" {}\n", &pkg.name).as_bytes())?;
-            written = true;
-            let (_, repo) = pkg.repo();
-            let msg = format!(
+            out.push_str(&format!(
                 "command! -nargs=* -range -bang {cmd} packadd {repo} | \
                  call s:do_cmd('{cmd}', \"<bang>\", <line1>, <line2>, <q-args>)\n\n"
-            );
-            plugin_file.write_all(msg.as_bytes())?;
+            ));
         }
 
         if !pkg.for_types.is_empty() {
-            if !written {
-                plugin_file.write_all(format!("\" {}\n", &pkg.name).as_bytes())?;
-                written = true;
-            }
-            let (_, repo) = pkg.repo();
             let types = pkg.for_types.join(",");
-            let msg = format!("autocmd FileType {types} packadd {repo}\n\n");
-            plugin_file.write_all(msg.as_bytes())?;
+            out.push_str(&format!("autocmd FileType {types} packadd {repo}\n\n"));
         }
 
-        if path.is_file() {
-            File::open(&path)?.read_to_string(&mut buf)?;
-            if !written {
-                plugin_file.write_all(format!("\" {}\n", &pkg.name).as_bytes())?;
-            }
-            plugin_file.write_all(format!("{}\n", &buf).as_bytes())?;
+        if let Some(vim) = vim {
+            out.push_str(&format!("{vim}\n"));
+        }
+        if let Some(lua) = lua {
+            out.push_str(&format!("lua << NPACK_EOF\n{}\nNPACK_EOF\n\n", lua.trim_end()));
         }
     }
-    Ok(())
+    Ok(out)
+}
+
+fn lua_loader(packs: &[Package]) -> Result<String> {
+    let mut out = format!("{LUA_PLUGIN_HEADER}\n");
+    for pkg in packs {
+        let (vim, lua) = read_configs(pkg)?;
+        let has_loader = pkg.load_command.is_some() || !pkg.for_types.is_empty();
+        if !has_loader && vim.is_none() && lua.is_none() {
+            continue;
+        }
+        out.push_str(&format!("-- {}\n", &pkg.name));
+        let (_, repo) = pkg.repo();
+
+        if let Some(ref cmd) = pkg.load_command {
+            out.push_str(&format!("pack_command({}, {})\n", lua_str(cmd), lua_str(repo)));
+        }
+
+        if !pkg.for_types.is_empty() {
+            let types = pkg
+                .for_types
+                .iter()
+                .map(|t| lua_str(t))
+                .collect::<Vec<String>>()
+                .join(", ");
+            out.push_str(&format!(
+                "vim.api.nvim_create_autocmd('FileType', {{ group = group, pattern = {{ {types} }}, command = {} }})\n",
+                lua_str(&format!("packadd {repo}"))
+            ));
+        }
+
+        if let Some(vim) = vim {
+            out.push_str(&format!("vim.cmd([==[\n{}\n]==])\n", vim.trim_end()));
+        }
+        if let Some(lua) = lua {
+            out.push_str(&format!("do\n{}\nend\n", lua.trim_end()));
+        }
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Quote `s` as a Lua string literal.
+pub(crate) fn lua_str(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
 }
 
 fn read_dir<H>(dir: &Path, mut action: H) -> Result<()>
@@ -376,7 +830,7 @@ where
     F: Fn(&str, &str, &str),
 {
     read_dir(&PACK_DIR, |path, cate| {
-        let is_match = category.as_ref().map_or(true, |c| *c == cate);
+        let is_match = category.as_ref().is_none_or(|c| *c == cate);
         if is_match {
             read_dir(path, |subpath, option| {
                 if (start && option != "start")
@@ -402,6 +856,142 @@ where
 mod tests {
     use super::*;
 
+    #[test]
+    fn package_pin_precedence() {
+        let mut p = Package::new("user/reponame", "", false);
+        assert_eq!(p.pin(), git::Pin::Head);
+        p.set_version("^1.2");
+        assert_eq!(p.pin(), git::Pin::Version("^1.2".to_string()));
+        p.set_tag("v1.2.3");
+        assert_eq!(p.pin(), git::Pin::Tag("v1.2.3".to_string()));
+        p.set_commit("abcdef");
+        assert_eq!(p.pin(), git::Pin::Commit("abcdef".to_string()));
+    }
+
+    #[test]
+    fn package_depth_overrides_settings() {
+        let settings = Settings {
+            depth: Some(1),
+            ..Settings::default()
+        };
+        let mut p = Package::new("user/reponame", "", false);
+        assert_eq!(p.spec(&settings).depth, Some(1));
+        p.set_depth(0);
+        assert_eq!(p.spec(&settings).depth, None);
+        p.set_depth(5);
+        assert_eq!(p.spec(&Settings::default()).depth, Some(5));
+    }
+
+    #[test]
+    fn package_rtp_repo_path() {
+        let docs = YamlLoader::load_from_str(
+            "name: user/tool\ncategory: default\nopt: false\nsubdir: editors/vim/",
+        )
+        .unwrap();
+        let p = Package::from_yaml(&docs[0]).unwrap();
+        assert_eq!(p.rtp.as_deref(), Some("editors/vim"));
+        for rtp in ["/etc", "../other", "vim/../../other"] {
+            let doc = format!("name: user/tool\ncategory: default\nopt: false\nrtp: {rtp}");
+            let docs = YamlLoader::load_from_str(&doc).unwrap();
+            assert!(Package::from_yaml(&docs[0]).is_err());
+        }
+        assert_eq!(p.repo_path(), PACK_REPOS_DIR.join("user-tool"));
+        assert_ne!(p.repo_path(), p.path());
+
+        let p = Package::new("user/tool", "default", false);
+        assert_eq!(p.repo_path(), p.path());
+    }
+
+    #[test]
+    fn package_config_paths() {
+        let p = Package::new("user/foo.vim", "default", false);
+        assert_eq!(p.config_path(), PACK_CONFIG_DIR.join("user-foo.vim"));
+        assert_eq!(p.lua_config_path(), PACK_CONFIG_DIR.join("user-foo.lua"));
+        assert_eq!(lua_str(r"it's a\b"), r"'it\'s a\\b'");
+    }
+
+    #[test]
+    fn packfile_migration() {
+        let docs = YamlLoader::load_from_str(
+            "- name: user/a\n  for: \"rust, toml\"\n- name: user/b\n  category: lang\n  opt: true",
+        )
+        .unwrap();
+        let (doc, from) = migrate(docs[0].clone()).unwrap();
+        assert_eq!(from, 0);
+        assert_eq!(doc["version"].as_i64(), Some(PACKFILE_VERSION));
+        let a = Package::from_yaml(&doc["packages"][0]).unwrap();
+        assert_eq!((a.category.as_str(), a.opt), ("default", false));
+        assert_eq!(a.for_types, vec!["rust", "toml"]);
+        assert_eq!(a.submodules.exclude, vec!["docs"]);
+        let b = Package::from_yaml(&doc["packages"][1]).unwrap();
+        assert_eq!((b.category.as_str(), b.opt), ("lang", true));
+
+        let (_, from) = migrate(doc).unwrap();
+        assert_eq!(from, PACKFILE_VERSION);
+
+        let docs = YamlLoader::load_from_str("version: 99\npackages: []").unwrap();
+        assert!(migrate(docs[0].clone()).is_err());
+
+        let path = env::temp_dir().join(format!("npack-packfile-{}", process::id()));
+        fs::write(&path, format!("version: {PACKFILE_VERSION}\npackage: []")).unwrap();
+        let res = fetch_from_packfile(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn package_local_mode() {
+        let docs = YamlLoader::load_from_str(
+            "name: /src/plugin\ncategory: default\nopt: false\nlocal: true\nlocal_mode: copy",
+        )
+        .unwrap();
+        let p = Package::from_yaml(&docs[0]).unwrap();
+        assert_eq!(p.local_mode, LocalMode::Copy);
+        let doc = p.into_yaml();
+        assert_eq!(doc["local_mode"].as_str(), Some("copy"));
+        assert_eq!(Package::new("a", "b", false).local_mode, LocalMode::Symlink);
+    }
+
+    #[test]
+    fn package_verify_signature() {
+        let docs = YamlLoader::load_from_str(
+            "name: user/repo\ncategory: default\nopt: false\nverify: signature",
+        )
+        .unwrap();
+        let p = Package::from_yaml(&docs[0]).unwrap();
+        assert!(p.spec(&Settings::default()).verify.is_some());
+        assert!(Package::new("user/repo", "", false).spec(&Settings::default()).verify.is_none());
+
+        let docs = YamlLoader::load_from_str(
+            "name: user/repo\ncategory: default\nopt: false\nverify: yes",
+        )
+        .unwrap();
+        assert!(Package::from_yaml(&docs[0]).is_err());
+    }
+
+    #[test]
+    fn package_submodules_yaml() {
+        let docs = YamlLoader::load_from_str(
+            "name: user/repo
+category: default
+opt: false
+submodules:
+  exclude: [test/fixtures]",
+        )
+        .unwrap();
+        let p = Package::from_yaml(&docs[0]).unwrap();
+        assert!(p.submodules.allows("lib"));
+        assert!(!p.submodules.allows("test/fixtures"));
+
+        let back = Package::from_yaml(&p.clone().into_yaml()).unwrap();
+        assert_eq!(back.submodules, p.submodules);
+
+        let mut off = Package::new("user/repo", "default", false);
+        off.submodules.enabled = false;
+        let back = Package::from_yaml(&off.into_yaml()).unwrap();
+        assert!(!back.submodules.enabled);
+    }
+
     #[test]
     fn package_path_user_repo() {
         let p = Package::new("user/reponame", "", false);
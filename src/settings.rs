@@ -0,0 +1,220 @@
+use crate::git;
+use crate::package::PACK_CONFIG_DIR;
+use crate::{Error, Result};
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use yaml_rust::{Yaml, YamlLoader};
+
+const DEFAULT_RETRIES: u32 = 0;
+const DEFAULT_RETRY_DELAY: u32 = 1;
+
+lazy_static! {
+    static ref SETTINGS_FILE: PathBuf = (*PACK_CONFIG_DIR).join("settings");
+}
+
+/// Global settings read from `.pack/settings`.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Default history depth of clones and fetches
+    pub depth: Option<u32>,
+    /// Private keys used for ssh remotes when the ssh-agent has none
+    pub ssh_keys: Vec<PathBuf>,
+    /// Url prefixes replaced before they are used, like git's `insteadOf`
+    pub url_rewrites: Vec<(String, String)>,
+    /// How often a failed clone or fetch is repeated
+    pub retries: u32,
+    /// Seconds to wait before the first retry, doubled for every further one
+    pub retry_delay: u32,
+    /// Seconds after which a clone or fetch is cancelled
+    pub timeout: Option<u32>,
+    /// Clone and fetch through shared mirrors in the user's cache directory
+    pub mirror_cache: bool,
+    /// Only use the mirror cache, set by `--offline`
+    pub offline: bool,
+    /// Trusted keys for packages with `verify: signature`
+    pub verify: git::Verify,
+    /// Generate `plugin/_pack.lua` instead of `plugin/_pack.vim`
+    pub lua_loader: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            depth: None,
+            ssh_keys: Vec::new(),
+            url_rewrites: Vec::new(),
+            retries: DEFAULT_RETRIES,
+            retry_delay: DEFAULT_RETRY_DELAY,
+            timeout: None,
+            mirror_cache: false,
+            offline: false,
+            verify: git::Verify::default(),
+            lua_loader: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn from_yaml(doc: &Yaml) -> Result<Settings> {
+        let depth = optional_u32(&doc["depth"])?;
+        let ssh_keys = string_list(&doc["ssh_keys"])?
+            .iter()
+            .map(|k| expand_home(k))
+            .collect();
+        let url_rewrites = match &doc["url_rewrites"] {
+            Yaml::BadValue | Yaml::Null => vec![],
+            Yaml::Hash(h) => h
+                .iter()
+                .map(|(k, v)| match (k.as_str(), v.as_str()) {
+                    (Some(k), Some(v)) => Ok((k.to_string(), v.to_string())),
+                    _ => Err(Error::Format),
+                })
+                .collect::<Result<Vec<(String, String)>>>()?,
+            _ => return Err(Error::Format),
+        };
+        Ok(Settings {
+            depth,
+            ssh_keys,
+            url_rewrites,
+            retries: optional_u32(&doc["retries"])?.unwrap_or(DEFAULT_RETRIES),
+            retry_delay: optional_u32(&doc["retry_delay"])?.unwrap_or(DEFAULT_RETRY_DELAY),
+            timeout: optional_u32(&doc["timeout"])?.filter(|t| *t > 0),
+            mirror_cache: match &doc["mirror_cache"] {
+                Yaml::BadValue | Yaml::Null => false,
+                d => d.as_bool().ok_or(Error::Format)?,
+            },
+            offline: false,
+            verify: git::Verify {
+                gpg_home: doc["gpg_home"].as_str().map(expand_home),
+                allowed_signers: doc["allowed_signers"].as_str().map(expand_home),
+            },
+            lua_loader: match doc["loader"].as_str() {
+                None | Some("vim") => false,
+                Some("lua") => true,
+                Some(_) => return Err(Error::Format),
+            },
+        })
+    }
+
+    /// Apply the rewrite with the longest matching prefix to `url`.
+    pub fn rewrite_url(&self, url: &str) -> String {
+        self.url_rewrites
+            .iter()
+            .filter(|(prefix, _)| url.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(prefix, replacement)| format!("{replacement}{}", &url[prefix.len()..]))
+            .unwrap_or_else(|| url.to_string())
+    }
+}
+
+/// Read an optional list of strings.
+pub fn string_list(doc: &Yaml) -> Result<Vec<String>> {
+    match doc {
+        Yaml::BadValue | Yaml::Null => Ok(vec![]),
+        Yaml::Array(items) => items
+            .iter()
+            .map(|e| e.as_str().map(|s| s.to_string()).ok_or(Error::Format))
+            .collect(),
+        _ => Err(Error::Format),
+    }
+}
+
+/// Replace a leading `~` with the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Read an optional non negative integer.
+pub fn optional_u32(doc: &Yaml) -> Result<Option<u32>> {
+    match doc {
+        Yaml::BadValue | Yaml::Null => Ok(None),
+        d => d
+            .as_i64()
+            .and_then(|i| u32::try_from(i).ok())
+            .map(Some)
+            .ok_or(Error::Format),
+    }
+}
+
+/**
+ * Load the settings file if one is available.
+ */
+pub fn fetch() -> Result<Settings> {
+    if SETTINGS_FILE.is_file() {
+        fetch_from_file(&*SETTINGS_FILE)
+            .map_err(|e| Error::PackFile(format!("Fail to parse settings: {e}")))
+    } else {
+        Ok(Settings::default())
+    }
+}
+
+fn fetch_from_file<P: AsRef<Path>>(path: P) -> Result<Settings> {
+    let mut data = String::new();
+    File::open(path.as_ref())?.read_to_string(&mut data)?;
+    let docs = YamlLoader::load_from_str(&data)?;
+    match docs.first() {
+        Some(doc) => Settings::from_yaml(doc),
+        None => Ok(Settings::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_depth() {
+        let docs = YamlLoader::load_from_str("depth: 1").unwrap();
+        assert_eq!(Settings::from_yaml(&docs[0]).unwrap().depth, Some(1));
+
+        let docs = YamlLoader::load_from_str("depth: -1").unwrap();
+        assert!(Settings::from_yaml(&docs[0]).is_err());
+    }
+
+    #[test]
+    fn settings_retries() {
+        let docs = YamlLoader::load_from_str("depth: 1").unwrap();
+        let settings = Settings::from_yaml(&docs[0]).unwrap();
+        assert_eq!((settings.retries, settings.retry_delay, settings.timeout), (0, 1, None));
+
+        let docs = YamlLoader::load_from_str("retries: 2\ntimeout: 30").unwrap();
+        let settings = Settings::from_yaml(&docs[0]).unwrap();
+        assert_eq!((settings.retries, settings.timeout), (2, Some(30)));
+    }
+
+    #[test]
+    fn settings_rewrite_url() {
+        let docs = YamlLoader::load_from_str(
+            "url_rewrites:
+  'https://github.com/': 'https://mirror.local/github/'
+  'https://github.com/vendor/': 'git@vendor.local:'",
+        )
+        .unwrap();
+        let settings = Settings::from_yaml(&docs[0]).unwrap();
+        assert_eq!(
+            settings.rewrite_url("https://github.com/user/repo"),
+            "https://mirror.local/github/user/repo"
+        );
+        assert_eq!(
+            settings.rewrite_url("https://github.com/vendor/repo"),
+            "git@vendor.local:repo"
+        );
+        assert_eq!(settings.rewrite_url("https://gitlab.com/a/b"), "https://gitlab.com/a/b");
+    }
+
+    #[test]
+    fn settings_ssh_keys() {
+        let docs = YamlLoader::load_from_str("ssh_keys: [/keys/id_ed25519, ~/.ssh/id_rsa]").unwrap();
+        let settings = Settings::from_yaml(&docs[0]).unwrap();
+        assert_eq!(settings.ssh_keys[0], PathBuf::from("/keys/id_ed25519"));
+        assert!(settings.ssh_keys[1].ends_with(".ssh/id_rsa"));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::echo;
+use crate::git;
 use crate::package::Package;
 use crate::utils::Spinner;
 use crate::Error;
@@ -10,6 +11,7 @@ use std::convert::TryFrom;
 use std::fs;
 use std::io;
 use std::io::Write;
+use std::path::PathBuf;
 use std::process;
 use std::sync::{Arc, Mutex};
 use std::thread;
@@ -29,6 +31,8 @@ pub enum TaskError {
 pub enum TaskType {
     Install,
     Update,
+    /// Only inspect the packages, nothing on disk changes
+    Outdated,
 }
 
 pub struct TaskManager {
@@ -51,10 +55,17 @@ impl TaskManager {
         self.packs.push(pack);
     }
 
+    pub fn packages(&self) -> &[Package] {
+        &self.packs
+    }
+
     /// returns true on success otherwise false
-    fn update<F>(pack: &Package, line: u16, func: F) -> bool
+    ///
+    /// Only installs and updates run the build command, an outdated check
+    /// leaves its own status on the line.
+    fn update<F>(pack: &Package, line: u16, func: &F, build: bool) -> bool
     where
-        F: Fn(&Package) -> (Result<(), Error>, bool),
+        F: Fn(&Package, echo::Line) -> (Result<(), Error>, bool),
     {
         const MSG_MARGIN: u16 = 5;
         const SIGN_MARGIN: u16 = 3;
@@ -74,12 +85,16 @@ impl TaskManager {
 
         let mut successful = true;
         let spinner = Spinner::spin(line, SIGN_MARGIN);
-        if let (Err(e), status) = func(pack) {
+        let status_line = echo::Line {
+            line,
+            offset: MSG_MARGIN + pos,
+        };
+        if let (Err(e), status) = func(pack, status_line) {
             spinner.stop();
             print_err!(e);
             successful = status;
         } else {
-            if pack.build_command.is_some() {
+            if build && pack.build_command.is_some() {
                 echo::inline_message(line, MSG_MARGIN + pos, "building");
                 if let Err(e) = pack.try_build().map_err(|e| Error::build(format!("{e}"))) {
                     print_err!(e);
@@ -89,7 +104,9 @@ impl TaskManager {
             spinner.stop();
             if successful {
                 echo::character(line, SIGN_MARGIN, '✓', color::Green);
-                echo::inline_message(line, MSG_MARGIN + pos, "done");
+                if build {
+                    echo::inline_message(line, MSG_MARGIN + pos, "done");
+                }
             }
         }
         successful
@@ -97,7 +114,7 @@ impl TaskManager {
 
     pub fn run<F>(self, func: F) -> Result<Vec<String>, TaskError>
     where
-        F: Fn(&Package) -> (Result<(), Error>, bool) + Send + 'static + Copy,
+        F: Fn(&Package, echo::Line) -> (Result<(), Error>, bool) + Send + Sync + 'static,
     {
         if self.packs.is_empty() {
             return Err(TaskError::NoPlugins);
@@ -110,8 +127,10 @@ impl TaskManager {
         }
 
         let quit_notifier = setup_signal()?;
+        let func = Arc::new(func);
 
         let threads = self.thread_num;
+        let build = !matches!(self.task_type, TaskType::Outdated);
 
         let wg = WaitGroup::new();
         let (tx, rx) = bounded::<Option<Package>>(threads);
@@ -125,6 +144,7 @@ impl TaskManager {
             let pending = pending.clone();
             let wg = wg.clone();
             let quit_notifier = quit_notifier.clone();
+            let func = func.clone();
             thread::spawn(move || {
                 while let Ok(Some(pack)) = rx.recv() {
                     log::info!("pack {}", &pack.name);
@@ -132,16 +152,17 @@ impl TaskManager {
                     {
                         let mut p = pending.lock().expect("To get access to Lock");
                         log::info!("add to pending:{}", &pack.name);
-                        p.push(pack.clone());
+                        p.push((pack.name.clone(), new_paths(&pack)));
                     }
 
                     let name = pack.name.clone();
                     let failures = failures.clone();
+                    let func = func.clone();
 
                     let (wtx, wrx) = bounded(0);
                     thread::spawn(move || {
                         let index = echo::line();
-                        if !Self::update(&pack, index, func) {
+                        if !Self::update(&pack, index, &*func, build) {
                             let mut f = failures.lock().expect("To get access to Lock");
                             f.push(pack.name);
                         }
@@ -157,7 +178,7 @@ impl TaskManager {
                     {
                         let mut p = pending.lock().expect("To get access to Lock");
                         log::info!("remove from pending: {}", &name);
-                        p.retain(|x| x.name != name);
+                        p.retain(|(x, _)| *x != name);
                     }
                 }
             });
@@ -181,12 +202,24 @@ impl TaskManager {
 
         log::info!("quit");
 
-        helptags();
+        if !matches!(self.task_type, TaskType::Outdated) {
+            helptags();
+        }
+
+        let retried = git::take_retried();
+        if !retried.is_empty() {
+            println!("Succeeded after retrying:");
+            for url in retried {
+                println!("    {url}");
+            }
+        }
 
         if let TaskType::Install = self.task_type {
-            for p in pending.lock().expect("To get access to Lock").iter() {
-                log::info!("delete {:?}", p.path());
-                let _ = fs::remove_dir_all(p.path());
+            for (_, paths) in pending.lock().expect("To get access to Lock").iter() {
+                for path in paths {
+                    log::info!("delete {:?}", path);
+                    let _ = fs::remove_dir_all(path);
+                }
             }
         }
 
@@ -195,6 +228,15 @@ impl TaskManager {
     }
 }
 
+/// The paths of `pack` which don't exist yet. An interrupted install only
+/// removes those, existing checkouts are kept.
+fn new_paths(pack: &Package) -> Vec<PathBuf> {
+    let mut paths = vec![pack.path(), pack.repo_path()];
+    paths.dedup();
+    paths.retain(|p| p.symlink_metadata().is_err());
+    paths
+}
+
 const NVIM_CMDS: &str = "
 :helptags ALL
 :TSUpdate
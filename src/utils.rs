@@ -2,7 +2,8 @@ use crate::echo;
 use crate::{Error, Result};
 
 use std::env;
-use std::fs;
+use std::fs::{self, File};
+use std::os::unix::fs::{symlink, PermissionsExt};
 use std::path::Path;
 use std::process;
 use std::sync::mpsc::{channel, Sender};
@@ -57,6 +58,77 @@ pub fn copy_directory<P: AsRef<Path>>(src: P, dst: P) -> Result<()> {
     Ok(())
 }
 
+/// Make `dst` a copy of `src`, keeping file modes and symlinks.
+///
+/// Files are only copied if their size or modification time differ, entries
+/// missing in `src` are removed. Returns the number of changed entries.
+pub fn sync_directory<P: AsRef<Path>>(src: P, dst: P) -> Result<usize> {
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    let mut changed = 0;
+    // Directories stay writable until their contents are synced, the modes
+    // of `src` are applied at the end.
+    let mut dirs = Vec::new();
+    for entry in WalkDir::new(src).follow_links(false) {
+        let e = entry?;
+        let target = dst.join(e.path().strip_prefix(src)?);
+        let meta = e.metadata()?;
+        let existing = target.symlink_metadata().ok();
+        if meta.is_dir() {
+            if !existing.as_ref().is_some_and(|m| m.is_dir()) {
+                remove_entry(&target)?;
+                fs::create_dir_all(&target)?;
+                changed += 1;
+            }
+            let mode = fs::metadata(&target)?.permissions().mode();
+            fs::set_permissions(&target, fs::Permissions::from_mode(mode | 0o700))?;
+            dirs.push((target, meta.permissions()));
+        } else if meta.file_type().is_symlink() {
+            let link = fs::read_link(e.path())?;
+            if fs::read_link(&target).ok().as_ref() != Some(&link) {
+                remove_entry(&target)?;
+                symlink(&link, &target)?;
+                changed += 1;
+            }
+        } else {
+            let unchanged = existing.is_some_and(|m| {
+                m.is_file() && m.len() == meta.len() && m.modified().ok() == meta.modified().ok()
+            });
+            if !unchanged {
+                remove_entry(&target)?;
+                fs::copy(e.path(), &target)?;
+                File::open(&target)?.set_modified(meta.modified()?)?;
+                changed += 1;
+            }
+        }
+    }
+
+    let mut stale = Vec::new();
+    for entry in WalkDir::new(dst).follow_links(false).contents_first(true) {
+        let e = entry?;
+        let origin = src.join(e.path().strip_prefix(dst)?);
+        if origin.symlink_metadata().is_err() {
+            stale.push(e.into_path());
+        }
+    }
+    for path in stale {
+        remove_entry(&path)?;
+        changed += 1;
+    }
+    for (dir, permissions) in dirs {
+        fs::set_permissions(dir, permissions)?;
+    }
+    Ok(changed)
+}
+
+fn remove_entry(path: &Path) -> Result<()> {
+    match path.symlink_metadata() {
+        Ok(m) if m.is_dir() => fs::remove_dir_all(path)?,
+        Ok(_) => fs::remove_file(path)?,
+        Err(_) => {}
+    }
+    Ok(())
+}
+
 fn get_editor() -> Option<String> {
     let term = env::var("TERM");
     if term.map(|t| t == "dumb").unwrap_or(true) {
@@ -80,3 +152,37 @@ pub fn open_editor<P: AsRef<Path>>(path: P) -> Result<()> {
         .wait()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sync_read_only_directory() {
+        let root = env::temp_dir().join(format!("npack-sync-{}", process::id()));
+        let (src, dst) = (root.join("src"), root.join("dst"));
+        fs::create_dir_all(src.join("doc")).unwrap();
+        fs::write(src.join("doc/plugin.txt"), "help").unwrap();
+        symlink("doc/plugin.txt", src.join("link")).unwrap();
+        for dir in [src.join("doc"), src.clone()] {
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o555)).unwrap();
+        }
+
+        let res = sync_directory(&src, &dst);
+        let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        let modes = (mode(&dst), mode(&dst.join("doc")));
+        for dir in [&src, &dst] {
+            for sub in [dir.clone(), dir.join("doc")] {
+                let _ = fs::set_permissions(sub, fs::Permissions::from_mode(0o755));
+            }
+        }
+        let text = fs::read_to_string(dst.join("doc/plugin.txt"));
+        let link = fs::read_link(dst.join("link"));
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(res.unwrap(), 4);
+        assert_eq!(modes, (0o555, 0o555));
+        assert_eq!(text.unwrap(), "help");
+        assert_eq!(link.unwrap(), Path::new("doc/plugin.txt"));
+    }
+}